@@ -4,4 +4,23 @@ rg "WorldgenOresVanilla" Loader_Worldgen.java | sed -n 's/.*\.\([^"]*\)".*/\1/p'
 rg "WorldgenOresBedrock" Loader_Worldgen.java | sed -n 's/.*\.\([^"]*\)".*/\1/p' > oresBedrock.txt
 rg "WorldgenOresSmall" Loader_Worldgen.java | sed -n 's/.*\.\([^"]*\)".*/\1/p' > oresSmall.txt
 rg "WorldgenOresLarge" Loader_Worldgen.java | sed -n 's/.*\.\([^"]*\)".*/\1/p' > oresLarge.txt
+# 大型鉱脈の名前と、引数に並ぶ最初の 4 つの素材（主鉱石・副鉱石・中間層・散在）をタブ区切りで出力する
+# 素材は MT の識別子のまま出るので、表示名と異なるものを直してから data/large_veins.tsv に置く
+rg "WorldgenOresLarge" Loader_Worldgen.java | awk '
+{
+    if (!match($0, /"[^"]*"/)) next
+    name = substr($0, RSTART + 1, RLENGTH - 2)
+    sub(/.*\./, "", name)
+    rest = substr($0, RSTART + RLENGTH)
+    line = name
+    count = 0
+    while (count < 4 && match(rest, /MT\.[A-Za-z0-9_.]+/)) {
+        material = substr(rest, RSTART, RLENGTH)
+        sub(/.*\./, "", material)
+        line = line "\t" material
+        count++
+        rest = substr(rest, RSTART + RLENGTH)
+    }
+    if (count == 4) print line
+}' > largeVeins.tsv
 echo "Extraction Complete"
@@ -0,0 +1,6 @@
+ALTER TABLE sessions
+    DROP INDEX idx_user_id,
+    DROP COLUMN user_id,
+    DROP COLUMN user_agent,
+    DROP COLUMN ip_address,
+    DROP COLUMN last_seen_at;
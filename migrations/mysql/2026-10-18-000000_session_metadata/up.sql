@@ -0,0 +1,7 @@
+-- ログイン中の端末を一覧できるよう、セッションに利用者と接続元を記録する
+ALTER TABLE sessions
+    ADD COLUMN user_id VARCHAR(36) NULL,
+    ADD COLUMN user_agent VARCHAR(512) NULL,
+    ADD COLUMN ip_address VARCHAR(45) NULL,
+    ADD COLUMN last_seen_at TIMESTAMP NULL,
+    ADD INDEX idx_user_id (user_id);
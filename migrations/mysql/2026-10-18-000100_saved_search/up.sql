@@ -0,0 +1,10 @@
+-- 利用者ごとに名前を付けて保存した検索条件（クエリ文字列）
+CREATE TABLE saved_search (
+    id VARCHAR(36) PRIMARY KEY,
+    user_id VARCHAR(36) NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    query_string VARCHAR(2048) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_saved_search_user_name (user_id, name),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
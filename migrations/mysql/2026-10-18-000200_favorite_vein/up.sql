@@ -0,0 +1,10 @@
+-- 利用者がホーム画面に固定したお気に入りの鉱脈
+CREATE TABLE favorite_vein (
+    id VARCHAR(36) PRIMARY KEY,
+    user_id VARCHAR(36) NOT NULL,
+    vein_id VARCHAR(36) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_favorite_vein_user_vein (user_id, vein_id),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE,
+    FOREIGN KEY (vein_id) REFERENCES vein(id) ON DELETE CASCADE
+);
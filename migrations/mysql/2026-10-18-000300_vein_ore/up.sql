@@ -0,0 +1,11 @@
+-- 鉱脈に含まれる鉱石と層（主鉱石・副鉱石・中間層・散在）。履歴は持たず、現在の構成のみを保存する
+CREATE TABLE vein_ore (
+    id VARCHAR(36) PRIMARY KEY,
+    vein_id VARCHAR(36) NOT NULL,
+    material VARCHAR(64) NOT NULL,
+    role VARCHAR(16) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_vein_ore_vein_id (vein_id),
+    INDEX idx_vein_ore_material (material),
+    FOREIGN KEY (vein_id) REFERENCES vein(id) ON DELETE CASCADE
+);
@@ -0,0 +1,5 @@
+ALTER TABLE vein ADD COLUMN y_coord INT DEFAULT NULL;
+UPDATE vein SET y_coord = y_min;
+ALTER TABLE vein
+    DROP COLUMN y_min,
+    DROP COLUMN y_max;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE invitation (
+    id VARCHAR(36) PRIMARY KEY,
+    email VARCHAR(255),
+    token VARCHAR(36) UNIQUE NOT NULL,
+    invited_by VARCHAR(255) NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP NULL,
+    used_by VARCHAR(36) NULL REFERENCES "user"(id) ON DELETE SET NULL,
+    created_at TIMESTAMP DEFAULT (now() AT TIME ZONE 'utc')
+);
+CREATE INDEX idx_invitation_email ON invitation (email);
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE vein (
+    id VARCHAR(36) PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    x_coord INTEGER NOT NULL,
+    y_coord INTEGER DEFAULT NULL,
+    z_coord INTEGER NOT NULL,
+    created_at TIMESTAMP DEFAULT (now() AT TIME ZONE 'utc')
+);
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE vein_depletion (
+    id VARCHAR(36) PRIMARY KEY,
+    vein_id VARCHAR(36) NOT NULL REFERENCES vein(id) ON DELETE CASCADE,
+    depleted BOOLEAN DEFAULT FALSE,
+    created_at TIMESTAMP DEFAULT (now() AT TIME ZONE 'utc')
+);
+CREATE INDEX idx_vein_depletion_vein_id ON vein_depletion (vein_id);
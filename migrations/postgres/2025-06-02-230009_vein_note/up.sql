@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE vein_note (
+    id VARCHAR(36) PRIMARY KEY,
+    vein_id VARCHAR(36) NOT NULL REFERENCES vein(id) ON DELETE CASCADE,
+    note VARCHAR(255) DEFAULT NULL,
+    created_at TIMESTAMP DEFAULT (now() AT TIME ZONE 'utc')
+);
+CREATE INDEX idx_vein_note_vein_id ON vein_note (vein_id);
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE vein_is_bedrock (
+    id VARCHAR(36) PRIMARY KEY,
+    vein_id VARCHAR(36) NOT NULL REFERENCES vein(id) ON DELETE CASCADE,
+    is_bedrock BOOLEAN DEFAULT FALSE,
+    created_at TIMESTAMP DEFAULT (now() AT TIME ZONE 'utc')
+);
+CREATE INDEX idx_vein_is_bedrock_vein_id ON vein_is_bedrock (vein_id);
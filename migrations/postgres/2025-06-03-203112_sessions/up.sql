@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE sessions (
+    id VARCHAR(255) PRIMARY KEY,
+    data TEXT NOT NULL,
+    expiry_date TIMESTAMP NOT NULL
+);
+CREATE INDEX idx_sessions_expiry_date ON sessions (expiry_date);
@@ -0,0 +1,6 @@
+DROP INDEX idx_sessions_user_id;
+ALTER TABLE sessions
+    DROP COLUMN user_id,
+    DROP COLUMN user_agent,
+    DROP COLUMN ip_address,
+    DROP COLUMN last_seen_at;
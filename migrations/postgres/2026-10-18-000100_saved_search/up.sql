@@ -0,0 +1,9 @@
+-- 利用者ごとに名前を付けて保存した検索条件（クエリ文字列）
+CREATE TABLE saved_search (
+    id VARCHAR(36) PRIMARY KEY,
+    user_id VARCHAR(36) NOT NULL REFERENCES "user"(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    query_string VARCHAR(2048) NOT NULL,
+    created_at TIMESTAMP DEFAULT (now() AT TIME ZONE 'utc'),
+    UNIQUE (user_id, name)
+);
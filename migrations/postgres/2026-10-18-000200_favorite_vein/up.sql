@@ -0,0 +1,8 @@
+-- 利用者がホーム画面に固定したお気に入りの鉱脈
+CREATE TABLE favorite_vein (
+    id VARCHAR(36) PRIMARY KEY,
+    user_id VARCHAR(36) NOT NULL REFERENCES "user"(id) ON DELETE CASCADE,
+    vein_id VARCHAR(36) NOT NULL REFERENCES vein(id) ON DELETE CASCADE,
+    created_at TIMESTAMP DEFAULT (now() AT TIME ZONE 'utc'),
+    UNIQUE (user_id, vein_id)
+);
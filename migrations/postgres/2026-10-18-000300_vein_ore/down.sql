@@ -0,0 +1 @@
+DROP TABLE vein_ore;
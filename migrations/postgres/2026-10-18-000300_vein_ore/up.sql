@@ -0,0 +1,10 @@
+-- 鉱脈に含まれる鉱石と層（主鉱石・副鉱石・中間層・散在）。履歴は持たず、現在の構成のみを保存する
+CREATE TABLE vein_ore (
+    id VARCHAR(36) PRIMARY KEY,
+    vein_id VARCHAR(36) NOT NULL REFERENCES vein(id) ON DELETE CASCADE,
+    material VARCHAR(64) NOT NULL,
+    role VARCHAR(16) NOT NULL,
+    created_at TIMESTAMP DEFAULT (now() AT TIME ZONE 'utc')
+);
+CREATE INDEX idx_vein_ore_vein_id ON vein_ore (vein_id);
+CREATE INDEX idx_vein_ore_material ON vein_ore (material);
@@ -0,0 +1,6 @@
+-- 鉱脈は高さに幅があるため、Y 座標を最小値と最大値で記録する
+ALTER TABLE vein
+    ADD COLUMN y_min INTEGER,
+    ADD COLUMN y_max INTEGER;
+UPDATE vein SET y_min = y_coord, y_max = y_coord;
+ALTER TABLE vein DROP COLUMN y_coord;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE user (
+    id VARCHAR(36) PRIMARY KEY NOT NULL,
+    username VARCHAR(50) UNIQUE NOT NULL,
+    email VARCHAR(255),
+    password_hash VARCHAR(255) NOT NULL,
+    is_admin BOOLEAN DEFAULT FALSE,
+    is_active BOOLEAN DEFAULT TRUE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    invited_by VARCHAR(255)
+);
+CREATE INDEX idx_user_email ON user (email);
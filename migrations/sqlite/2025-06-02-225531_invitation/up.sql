@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE invitation (
+    id VARCHAR(36) PRIMARY KEY NOT NULL,
+    email VARCHAR(255),
+    token CHAR(36) UNIQUE NOT NULL,
+    invited_by VARCHAR(255) NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP NULL,
+    used_by CHAR(36) NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (used_by) REFERENCES user(id) ON DELETE SET NULL
+);
+CREATE INDEX idx_invitation_email ON invitation (email);
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE vein_confirmation (
+    id VARCHAR(36) PRIMARY KEY NOT NULL,
+    vein_id VARCHAR(36) NOT NULL,
+    confirmed BOOLEAN DEFAULT FALSE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (vein_id) REFERENCES vein(id) ON DELETE CASCADE
+);
+CREATE INDEX idx_vein_confirmation_vein_id ON vein_confirmation (vein_id);
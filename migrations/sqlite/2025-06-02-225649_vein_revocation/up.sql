@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE vein_revocation (
+    id VARCHAR(36) PRIMARY KEY NOT NULL,
+    vein_id VARCHAR(36) NOT NULL,
+    revoked BOOLEAN DEFAULT FALSE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (vein_id) REFERENCES vein(id) ON DELETE CASCADE
+);
+CREATE INDEX idx_vein_revocation_vein_id ON vein_revocation (vein_id);
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE vein_note (
+    id VARCHAR(36) PRIMARY KEY NOT NULL,
+    vein_id VARCHAR(36) NOT NULL,
+    note VARCHAR(255) DEFAULT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (vein_id) REFERENCES vein(id) ON DELETE CASCADE
+);
+CREATE INDEX idx_vein_note_vein_id ON vein_note (vein_id);
@@ -0,0 +1,5 @@
+DROP INDEX idx_sessions_user_id;
+ALTER TABLE sessions DROP COLUMN user_id;
+ALTER TABLE sessions DROP COLUMN user_agent;
+ALTER TABLE sessions DROP COLUMN ip_address;
+ALTER TABLE sessions DROP COLUMN last_seen_at;
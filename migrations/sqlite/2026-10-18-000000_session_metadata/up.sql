@@ -0,0 +1,6 @@
+-- ログイン中の端末を一覧できるよう、セッションに利用者と接続元を記録する
+ALTER TABLE sessions ADD COLUMN user_id VARCHAR(36);
+ALTER TABLE sessions ADD COLUMN user_agent VARCHAR(512);
+ALTER TABLE sessions ADD COLUMN ip_address VARCHAR(45);
+ALTER TABLE sessions ADD COLUMN last_seen_at TIMESTAMP;
+CREATE INDEX idx_sessions_user_id ON sessions (user_id);
@@ -0,0 +1,4 @@
+ALTER TABLE vein ADD COLUMN y_coord INTEGER DEFAULT NULL;
+UPDATE vein SET y_coord = y_min;
+ALTER TABLE vein DROP COLUMN y_min;
+ALTER TABLE vein DROP COLUMN y_max;
@@ -1,100 +1,171 @@
-use axum::{
-    Router, middleware,
-    routing::{get, post},
-};
-use axum_login::AuthManagerLayerBuilder;
-use tower_sessions::{Expiry, SessionManagerLayer, cookie::time::Duration};
-
-use crate::auth::backend::AuthBackend;
-use crate::auth::session_store::DieselSessionStore;
-use crate::auth::utils::SESSION_DURATION_DAYS;
-use crate::database::connection::AppState;
-use crate::handlers::auth::{
-    issue_invitation, login_handler, login_page, logout_handler, me_handler, register_handler,
-    register_page, require_admin, require_auth,
-};
-use crate::handlers::static_files::{serve_css, serve_index};
-use crate::handlers::vein::{
-    vein_confirmation_revoke, vein_confirmation_set, vein_depletion_revoke, vein_depletion_set,
-    vein_is_bedrock_revoke, vein_is_bedrock_set, vein_revocation_revoke, vein_revocation_set,
-};
-use crate::handlers::web::{add_vein_handler, issue_invitation_html, search_veins_handler};
-
-pub async fn create_app(state: AppState) -> anyhow::Result<Router> {
-    // セッションストアの初期化
-    let session_store = DieselSessionStore::new(state.diesel_pool.clone());
-
-    // セッション管理の設定（7日間有効）
-    let session_layer = SessionManagerLayer::new(session_store)
-        .with_expiry(Expiry::OnInactivity(Duration::days(SESSION_DURATION_DAYS)));
-
-    let auth_backend = AuthBackend::new(state.diesel_pool.clone());
-    auth_backend.check_users_and_generate_invitation().await?;
-
-    // 認証バックエンドの設定
-    let auth_layer = AuthManagerLayerBuilder::new(auth_backend, session_layer.clone()).build();
-
-    let app = Router::new()
-        .nest(
-            "/auth",
-            Router::new()
-                .route("/login", get(login_page))
-                .route("/login", post(login_handler))
-                .route("/logout", post(logout_handler))
-                .route("/register", get(register_page))
-                .route("/register", post(register_handler))
-                .route(
-                    "/issue-invitation",
-                    post(issue_invitation).layer(middleware::from_fn(require_admin)),
-                )
-                .route("/issue-invitation", get(issue_invitation_html)),
-        )
-        .nest(
-            "/api",
-            Router::new()
-                .route("/auth/me", get(me_handler))
-                .route(
-                    "/veins/{vein_id}/confirmation/set",
-                    post(vein_confirmation_set),
-                )
-                .route(
-                    "/veins/{vein_id}/confirmation/revoke",
-                    post(vein_confirmation_revoke),
-                )
-                .route("/veins/{vein_id}/depletion/set", post(vein_depletion_set))
-                .route(
-                    "/veins/{vein_id}/depletion/revoke",
-                    post(vein_depletion_revoke),
-                )
-                .route("/veins/{vein_id}/revocation/set", post(vein_revocation_set))
-                .route(
-                    "/veins/{vein_id}/revocation/revoke",
-                    post(vein_revocation_revoke),
-                )
-                .route("/veins/{vein_id}/is_bedrock/set", post(vein_is_bedrock_set))
-                .route(
-                    "/veins/{vein_id}/is_bedrock/revoke",
-                    post(vein_is_bedrock_revoke),
-                )
-                .route("/veins/add", post(add_vein_handler))
-                .layer(middleware::from_fn(require_auth)),
-        )
-        .route(
-            "/search",
-            get(search_veins_handler).layer(middleware::from_fn(require_auth)),
-        )
-        .route(
-            "/",
-            get(serve_index).layer(middleware::from_fn(require_auth)),
-        )
-        .route(
-            "/index.html",
-            get(serve_index).layer(middleware::from_fn(require_auth)),
-        )
-        .route("/styles.css", get(serve_css))
-        .layer(auth_layer)
-        .layer(session_layer)
-        .with_state(state);
-
-    Ok(app)
-}
+use axum::{
+    Router, middleware,
+    routing::{get, post},
+};
+use axum_login::AuthManagerLayerBuilder;
+use tower_sessions::{Expiry, SessionManagerLayer, cookie::time::Duration};
+
+use crate::auth::backend::AuthBackend;
+use crate::auth::csrf::require_csrf;
+use crate::auth::session_store::DieselSessionStore;
+use crate::database::connection::AppState;
+use crate::error::render_error_pages;
+use crate::handlers::auth::{
+    issue_invitation, lockouts_page, login_handler, login_page, logout_handler, me_handler,
+    register_handler, register_page, require_admin, require_auth, unlock_handler,
+};
+use crate::handlers::health::{healthz, readyz};
+use crate::handlers::materials::materials_page;
+use crate::handlers::saved_searches::{delete_saved_search_handler, save_search_handler};
+use crate::handlers::sessions::{
+    record_session_activity, revoke_other_sessions_handler, revoke_session_handler, sessions_page,
+};
+use crate::handlers::static_files::{serve_css, serve_static};
+use crate::handlers::vein::{
+    vein_confirmation_revoke, vein_confirmation_set, vein_depletion_revoke, vein_depletion_set,
+    vein_favorite_revoke, vein_favorite_set, vein_is_bedrock_revoke, vein_is_bedrock_set,
+    vein_revocation_revoke, vein_revocation_set,
+};
+use crate::handlers::web::{
+    add_vein_handler, issue_invitation_html, search_veins_handler, serve_index,
+};
+use crate::logging::trace_requests;
+use crate::metrics::{metrics_handler, track_metrics};
+
+pub async fn create_app(state: AppState) -> anyhow::Result<Router> {
+    // セッションストアの初期化
+    // 期限切れのセッションの定期削除は、停止を管理する main で起動する
+    let session_store = DieselSessionStore::new(state.diesel_pool.clone());
+
+    // セッション管理の設定（既定では7日間有効）
+    let session_layer = SessionManagerLayer::new(session_store).with_expiry(Expiry::OnInactivity(
+        Duration::days(state.config.session_duration_days),
+    ));
+
+    let auth_backend = AuthBackend::new(state.auth_repository.clone());
+    auth_backend
+        .check_users_and_generate_invitation(&state.config)
+        .await?;
+
+    // 認証バックエンドの設定
+    let auth_layer = AuthManagerLayerBuilder::new(auth_backend, session_layer.clone()).build();
+
+    let app = Router::new()
+        .nest(
+            "/auth",
+            Router::new()
+                .route("/login", get(login_page))
+                .route("/login", post(login_handler))
+                .route("/logout", post(logout_handler))
+                .route("/register", get(register_page))
+                .route("/register", post(register_handler))
+                .route(
+                    "/issue-invitation",
+                    post(issue_invitation).layer(middleware::from_fn(require_admin)),
+                )
+                .route("/issue-invitation", get(issue_invitation_html))
+                .route(
+                    "/lockouts",
+                    get(lockouts_page).layer(middleware::from_fn(require_admin)),
+                )
+                .route(
+                    "/lockouts/unlock",
+                    post(unlock_handler).layer(middleware::from_fn(require_admin)),
+                )
+                .route(
+                    "/sessions",
+                    get(sessions_page).layer(middleware::from_fn(require_auth)),
+                )
+                .route(
+                    "/sessions/revoke",
+                    post(revoke_session_handler).layer(middleware::from_fn(require_auth)),
+                )
+                .route(
+                    "/sessions/revoke-others",
+                    post(revoke_other_sessions_handler).layer(middleware::from_fn(require_auth)),
+                ),
+        )
+        .nest(
+            "/api",
+            Router::new()
+                .route("/auth/me", get(me_handler))
+                .route(
+                    "/veins/{vein_id}/confirmation/set",
+                    post(vein_confirmation_set),
+                )
+                .route(
+                    "/veins/{vein_id}/confirmation/revoke",
+                    post(vein_confirmation_revoke),
+                )
+                .route("/veins/{vein_id}/depletion/set", post(vein_depletion_set))
+                .route(
+                    "/veins/{vein_id}/depletion/revoke",
+                    post(vein_depletion_revoke),
+                )
+                .route("/veins/{vein_id}/revocation/set", post(vein_revocation_set))
+                .route(
+                    "/veins/{vein_id}/revocation/revoke",
+                    post(vein_revocation_revoke),
+                )
+                .route("/veins/{vein_id}/is_bedrock/set", post(vein_is_bedrock_set))
+                .route(
+                    "/veins/{vein_id}/is_bedrock/revoke",
+                    post(vein_is_bedrock_revoke),
+                )
+                .route("/veins/{vein_id}/favorite/set", post(vein_favorite_set))
+                .route(
+                    "/veins/{vein_id}/favorite/revoke",
+                    post(vein_favorite_revoke),
+                )
+                .route("/veins/add", post(add_vein_handler))
+                .route("/searches/save", post(save_search_handler))
+                .route(
+                    "/searches/{search_id}/delete",
+                    post(delete_saved_search_handler),
+                )
+                .layer(middleware::from_fn(require_auth)),
+        )
+        .route(
+            "/search",
+            get(search_veins_handler).layer(middleware::from_fn(require_auth)),
+        )
+        .route(
+            "/materials",
+            get(materials_page).layer(middleware::from_fn(require_auth)),
+        )
+        .route(
+            "/",
+            get(serve_index).layer(middleware::from_fn(require_auth)),
+        )
+        .route(
+            "/index.html",
+            get(serve_index).layer(middleware::from_fn(require_auth)),
+        )
+        .route(
+            "/metrics",
+            get(metrics_handler).layer(middleware::from_fn(require_admin)),
+        )
+        // プロセス監視用。ログインなしで応答する
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/styles.css", get(serve_css))
+        .route("/static/{*path}", get(serve_static))
+        // 状態を変更する全てのリクエストで CSRF トークンを検証する
+        .layer(middleware::from_fn(require_csrf))
+        // ログイン中の端末一覧のため、接続元と最終アクセスを記録する
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            record_session_activity,
+        ))
+        // エラー応答をリクエストに合わせて HTML か JSON で返す
+        .layer(middleware::from_fn(render_error_pages))
+        // リクエストごとのログ。ユーザー ID を参照するため認証レイヤーの内側に置く
+        .layer(middleware::from_fn(trace_requests))
+        // /metrics 用にルートごとのリクエスト数と処理時間を数える
+        .layer(middleware::from_fn_with_state(state.clone(), track_metrics))
+        .layer(auth_layer)
+        .layer(session_layer)
+        .with_state(state);
+
+    Ok(app)
+}
@@ -1,4 +1,7 @@
 pub mod backend;
+pub mod csrf;
 pub mod queries;
+pub mod repository;
 pub mod session_store;
+pub mod throttle;
 pub mod utils;
@@ -1,8 +1,11 @@
 use axum_login::{AuthUser, AuthnBackend, UserId};
-use diesel_async::{AsyncMysqlConnection, pooled_connection::deadpool::Pool};
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::sync::Arc;
 
+use crate::auth::repository::AuthRepository;
+use crate::config::AppConfig;
+use crate::database::repository::RepositoryError;
 use crate::models::auth::User;
 
 // Because authenticate function potentially returns multiple types of errors.
@@ -37,6 +40,15 @@ impl From<diesel_async::pooled_connection::deadpool::PoolError> for AuthError {
     }
 }
 
+impl From<RepositoryError> for AuthError {
+    fn from(error: RepositoryError) -> Self {
+        match error {
+            RepositoryError::Database(e) => AuthError::Database(e),
+            RepositoryError::Pool(e) => AuthError::Pool(e),
+        }
+    }
+}
+
 // For axum_login
 // axum requires the User type to implement AuthUser trait
 impl AuthUser for User {
@@ -51,24 +63,23 @@ impl AuthUser for User {
     }
 }
 
-pub type DbPool = Pool<AsyncMysqlConnection>;
+pub type DbPool = crate::database::connection::DieselPool;
 #[derive(Clone)]
 pub struct AuthBackend {
-    pub db: DbPool,
+    pub repository: Arc<dyn AuthRepository>,
 }
 
 impl AuthBackend {
-    pub fn new(db: DbPool) -> Self {
-        Self { db }
+    pub fn new(repository: Arc<dyn AuthRepository>) -> Self {
+        Self { repository }
     }
 
     /// This function has multiple purposes. I don't think this is a good design, but it works for now.
-    pub async fn check_users_and_generate_invitation(&self) -> anyhow::Result<()> {
-        use crate::auth::queries::AuthQueries;
-
-        let mut connection = self.db.get().await?;
-
-        let has_users = AuthQueries::has_any_users(&mut connection).await?;
+    pub async fn check_users_and_generate_invitation(
+        &self,
+        config: &AppConfig,
+    ) -> anyhow::Result<()> {
+        let has_users = self.repository.has_any_users().await?;
 
         // Changes behavior based on whether users already exist
         // To do initial setup, mainly for generating an admin invitation link
@@ -78,30 +89,21 @@ impl AuthBackend {
             println!("システムにユーザーが登録されていません。");
             println!("管理者用の招待リンクを生成します...\n");
 
-            // In case the environment variables are not set
-            let server_port = std::env::var("PORT").unwrap_or_else(|_| {
-                eprintln!("PORT environment variable not set, using default port 24528");
-                "24528".to_string()
-            });
-            let server_host = std::env::var("HOST").unwrap_or_else(|_| {
-                eprintln!("HOST environment variable not set, using default localhost");
-                "localhost".to_string()
-            });
-            let server_protocol = std::env::var("PROTOCOL").unwrap_or_else(|_| {
-                eprintln!("PROTOCOL environment variable not set, using default http");
-                "http".to_string()
-            });
-            let base_url = format!("{}://{}:{}", server_protocol, server_host, server_port);
-
-            match AuthQueries::create_system_invitation(&mut connection).await {
+            match self
+                .repository
+                .create_system_invitation(config.invitation_duration_hours)
+                .await
+            {
                 Ok(invitation) => {
-                    let invitation_url =
-                        format!("{}/auth/register?token={}", base_url, invitation.token);
+                    let invitation_url = config.invitation_url(&invitation.token);
 
                     println!("GT6 Vein Manager");
                     println!("初回管理者アカウント作成用の招待リンクを生成しました");
                     println!("{}", invitation_url);
-                    println!("この招待リンクは 7日間 有効です。");
+                    println!(
+                        "この招待リンクは {}時間 有効です。",
+                        config.invitation_duration_hours
+                    );
                     println!("管理者アカウントを作成後、他のユーザーを招待できます。");
                     println!("⚠️このリンクは一度だけ使用できます。安全に保管してください");
                     // 有効期限も表示
@@ -113,11 +115,11 @@ impl AuthBackend {
                     println!();
                 }
                 Err(e) => {
-                    println!("❌ 招待リンクの生成に失敗しました: {}", e);
+                    tracing::error!(error = %e, "Failed to create the initial admin invitation");
                 }
             }
         } else {
-            println!("✅ ユーザーが既に登録されています。");
+            tracing::info!("Users already exist, skipping initial setup");
         }
 
         Ok(())
@@ -140,11 +142,12 @@ impl AuthnBackend for AuthBackend {
         &self,
         creds: Self::Credentials,
     ) -> Result<Option<Self::User>, Self::Error> {
-        use crate::auth::{queries::AuthQueries, utils::verify_password};
+        use crate::auth::utils::verify_password;
 
-        let mut connection = self.db.get().await?;
-
-        let user = AuthQueries::get_user_by_username(&mut connection, &creds.username).await?;
+        let user = self
+            .repository
+            .get_user_by_username(&creds.username)
+            .await?;
 
         if let Some(user) = user {
             let is_valid = verify_password(&creds.password, &user.password_hash)
@@ -159,11 +162,7 @@ impl AuthnBackend for AuthBackend {
     }
 
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
-        use crate::auth::queries::AuthQueries;
-
-        let mut connection = self.db.get().await?;
-
-        Ok(AuthQueries::get_user_by_id(&mut connection, user_id).await?)
+        Ok(self.repository.get_user_by_id(user_id).await?)
     }
 }
 
@@ -0,0 +1,84 @@
+use axum::{
+    body::{Body, to_bytes},
+    extract::Request,
+    middleware::Next,
+    response::Response,
+};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+
+/// Session key under which the token is stored.
+const CSRF_SESSION_KEY: &str = "csrf_token";
+/// Name of the hidden form field carrying the token.
+pub const CSRF_FORM_FIELD: &str = "csrf_token";
+/// Header alternative for non-form clients.
+pub const CSRF_HEADER_NAME: &str = "x-csrf-token";
+/// Upper bound for buffering a form body while looking for the token.
+const MAX_FORM_BYTES: usize = 64 * 1024;
+
+/// Returns the CSRF token bound to the session, creating one on first use.
+pub async fn csrf_token(session: &Session) -> Result<String, tower_sessions::session::Error> {
+    if let Some(token) = session.get::<String>(CSRF_SESSION_KEY).await? {
+        return Ok(token);
+    }
+
+    let token = Uuid::new_v4().to_string();
+    session.insert(CSRF_SESSION_KEY, &token).await?;
+    Ok(token)
+}
+
+// CSRFトークン検証用ミドルウェア
+// GET などの安全なメソッド以外は、セッションに紐づくトークンと一致しなければ拒否する
+pub async fn require_csrf(session: Session, request: Request, next: Next) -> AppResult<Response> {
+    if request.method().is_safe() {
+        return Ok(next.run(request).await);
+    }
+
+    let expected = session.get::<String>(CSRF_SESSION_KEY).await?;
+
+    let (parts, body) = request.into_parts();
+    let bytes = to_bytes(body, MAX_FORM_BYTES)
+        .await
+        .map_err(|_| AppError::PayloadTooLarge("リクエストが大きすぎます".to_string()))?;
+
+    let submitted = parts
+        .headers
+        .get(CSRF_HEADER_NAME)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+        .or_else(|| form_token(&bytes));
+
+    match (expected, submitted) {
+        (Some(expected), Some(submitted)) if tokens_match(&expected, &submitted) => {
+            let request = Request::from_parts(parts, Body::from(bytes));
+            Ok(next.run(request).await)
+        }
+        _ => {
+            tracing::warn!("Rejected request due to missing or invalid CSRF token");
+            Err(AppError::Forbidden(
+                "不正なリクエストです。ページを再読み込みしてからやり直してください".to_string(),
+            ))
+        }
+    }
+}
+
+/// Picks the token out of an `application/x-www-form-urlencoded` body.
+fn form_token(bytes: &[u8]) -> Option<String> {
+    serde_urlencoded::from_bytes::<Vec<(String, String)>>(bytes)
+        .ok()?
+        .into_iter()
+        .find(|(key, _)| key == CSRF_FORM_FIELD)
+        .map(|(_, value)| value)
+}
+
+/// Compares without short-circuiting so the timing does not reveal the matching prefix.
+fn tokens_match(expected: &str, submitted: &str) -> bool {
+    expected.len() == submitted.len()
+        && expected
+            .bytes()
+            .zip(submitted.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
@@ -1,9 +1,10 @@
 use chrono::{Duration, Utc};
 use diesel::prelude::*;
-use diesel_async::{AsyncMysqlConnection, RunQueryDsl};
+use diesel_async::RunQueryDsl;
 use uuid::Uuid;
 
-use crate::auth::utils::{INVITATION_DURATION_HOURS, hash_password};
+use crate::auth::utils::hash_password;
+use crate::database::connection::{DbConnection, with_connection};
 use crate::models::auth::{Invitation, User};
 use crate::schema::*;
 use diesel::dsl::count_star;
@@ -13,41 +14,44 @@ pub struct AuthQueries;
 impl AuthQueries {
     /// システムにユーザーが存在するかチェック
     pub async fn has_any_users(
-        connection: &mut AsyncMysqlConnection,
+        connection: &mut DbConnection,
     ) -> Result<bool, diesel::result::Error> {
-        let count: i64 = user::table
-            .filter(user::is_active.eq(true))
-            .select(count_star())
-            .first::<i64>(connection)
-            .await?;
+        let count: i64 = with_connection!(connection, |connection| {
+            user::table
+                .filter(user::is_active.eq(true))
+                .select(count_star())
+                .first::<i64>(connection)
+                .await
+        })?;
 
         Ok(count > 0)
     }
 
     /// 管理者用の初期招待を作成（システム起動時用）
     pub async fn create_system_invitation(
-        connection: &mut AsyncMysqlConnection,
+        connection: &mut DbConnection,
+        valid_hours: u32,
     ) -> Result<Invitation, diesel::result::Error> {
-        println!("Creating system invitation...");
-
         let invitation_id = Uuid::new_v4().to_string();
         let token = Uuid::new_v4().to_string();
         let now = Utc::now().naive_utc();
-        let expires_at = now + Duration::hours((24 * INVITATION_DURATION_HOURS).into()); // 7 days
-
-        diesel::insert_into(invitation::table)
-            .values((
-                invitation::id.eq(&invitation_id),
-                invitation::email.eq(None::<String>), // System invitation, so NULL
-                invitation::token.eq(&token),
-                invitation::invited_by.eq::<Option<String>>(None), // System invitation, so NULL
-                invitation::expires_at.eq(expires_at),
-                invitation::created_at.eq(now),
-            ))
-            .execute(connection)
-            .await?;
-
-        println!("System invitation created: {}", invitation_id);
+        let expires_at = now + Duration::hours(valid_hours.into());
+
+        with_connection!(connection, |connection| {
+            diesel::insert_into(invitation::table)
+                .values((
+                    invitation::id.eq(&invitation_id),
+                    invitation::email.eq(None::<String>), // System invitation, so NULL
+                    invitation::token.eq(&token),
+                    invitation::invited_by.eq::<Option<String>>(None), // System invitation, so NULL
+                    invitation::expires_at.eq(expires_at),
+                    invitation::created_at.eq(now),
+                ))
+                .execute(connection)
+                .await
+        })?;
+
+        tracing::info!(invitation_id = %invitation_id, "System invitation created");
 
         Ok(Invitation {
             id: invitation_id,
@@ -63,39 +67,43 @@ impl AuthQueries {
 
     /// ユーザー名でユーザーを取得
     pub async fn get_user_by_username(
-        connection: &mut AsyncMysqlConnection,
+        connection: &mut DbConnection,
         username: &str,
     ) -> Result<Option<User>, diesel::result::Error> {
-        let user = user::table
-            .filter(user::username.eq(username))
-            .filter(user::is_active.eq(true))
-            .first::<User>(connection)
-            .await
-            .map_err(|_| diesel::result::Error::NotFound)
-            .optional()?;
+        let user = with_connection!(connection, |connection| {
+            user::table
+                .filter(user::username.eq(username))
+                .filter(user::is_active.eq(true))
+                .first::<User>(connection)
+                .await
+        })
+        .map_err(|_| diesel::result::Error::NotFound)
+        .optional()?;
 
         Ok(user)
     }
 
     /// UUIDでユーザーを取得
     pub async fn get_user_by_id(
-        connection: &mut AsyncMysqlConnection,
+        connection: &mut DbConnection,
         user_id: &str,
     ) -> Result<Option<User>, diesel::result::Error> {
-        let user = user::table
-            .filter(user::id.eq(user_id))
-            .filter(user::is_active.eq(true))
-            .first::<User>(connection)
-            .await
-            .map_err(|_| diesel::result::Error::NotFound)
-            .optional()?;
+        let user = with_connection!(connection, |connection| {
+            user::table
+                .filter(user::id.eq(user_id))
+                .filter(user::is_active.eq(true))
+                .first::<User>(connection)
+                .await
+        })
+        .map_err(|_| diesel::result::Error::NotFound)
+        .optional()?;
 
         Ok(user)
     }
 
     /// 新しいユーザーを作成
     pub async fn create_user(
-        connection: &mut AsyncMysqlConnection,
+        connection: &mut DbConnection,
         username: &str,
         email: Option<&str>,
         password: &str,
@@ -106,19 +114,21 @@ impl AuthQueries {
         let password_hash = hash_password(password).expect("パスワードのハッシュ化に失敗しました");
         let now = Utc::now().naive_utc();
 
-        diesel::insert_into(user::table)
-            .values((
-                user::id.eq(&user_id),
-                user::username.eq(username),
-                user::email.eq(email),
-                user::password_hash.eq(&password_hash),
-                user::is_admin.eq(is_admin),
-                user::is_active.eq(true),
-                user::created_at.eq(now),
-                user::invited_by.eq(invited_by),
-            ))
-            .execute(connection)
-            .await?;
+        with_connection!(connection, |connection| {
+            diesel::insert_into(user::table)
+                .values((
+                    user::id.eq(&user_id),
+                    user::username.eq(username),
+                    user::email.eq(email),
+                    user::password_hash.eq(&password_hash),
+                    user::is_admin.eq(is_admin),
+                    user::is_active.eq(true),
+                    user::created_at.eq(now),
+                    user::invited_by.eq(invited_by),
+                ))
+                .execute(connection)
+                .await
+        })?;
 
         let user = User {
             id: user_id,
@@ -136,15 +146,15 @@ impl AuthQueries {
 
     /// 招待を作成
     pub async fn create_invitation(
-        connection: &mut AsyncMysqlConnection,
+        connection: &mut DbConnection,
         email: Option<&str>,
         invited_by: Option<&str>,
+        valid_hours: u32,
     ) -> Result<Invitation, diesel::result::Error> {
-        println!("Attempting to create invitation");
         let invitation_id = Uuid::new_v4().to_string();
         let token = Uuid::new_v4().to_string();
         let now = Utc::now().naive_utc();
-        let expires_at = now + Duration::hours((24 * INVITATION_DURATION_HOURS).into()); // 7 days
+        let expires_at = now + Duration::hours(valid_hours.into());
         let invited_by = Some(
             invited_by
                 .filter(|s| !s.is_empty())
@@ -152,19 +162,20 @@ impl AuthQueries {
                 .to_string(),
         );
 
-        if let Err(e) = diesel::insert_into(invitation::table)
-            .values((
-                invitation::id.eq(&invitation_id),
-                invitation::email.eq(email),
-                invitation::token.eq(&token),
-                invitation::invited_by.eq(invited_by.as_deref()),
-                invitation::expires_at.eq(expires_at),
-                invitation::created_at.eq(now),
-            ))
-            .execute(connection)
-            .await
-        {
-            eprintln!("Failed to create invitation: {:?}", e);
+        if let Err(e) = with_connection!(connection, |connection| {
+            diesel::insert_into(invitation::table)
+                .values((
+                    invitation::id.eq(&invitation_id),
+                    invitation::email.eq(email),
+                    invitation::token.eq(&token),
+                    invitation::invited_by.eq(invited_by.as_deref()),
+                    invitation::expires_at.eq(expires_at),
+                    invitation::created_at.eq(now),
+                ))
+                .execute(connection)
+                .await
+        }) {
+            tracing::error!(error = %e, "Failed to create invitation");
             return Err(e);
         }
 
@@ -179,44 +190,67 @@ impl AuthQueries {
             created_at: Some(now),
         };
 
-        println!(
-            "Invitation created: {}, expires at: {}",
-            invitation.id, invitation.expires_at
+        tracing::info!(
+            invitation_id = %invitation.id,
+            expires_at = %invitation.expires_at,
+            "Invitation created"
         );
         Ok(invitation)
     }
 
     /// 招待トークンを取得・検証
     pub async fn get_invitation_by_token(
-        connection: &mut AsyncMysqlConnection,
+        connection: &mut DbConnection,
         token: &str,
     ) -> Result<Option<Invitation>, diesel::result::Error> {
-        let invitation = invitation::table
-            .filter(invitation::token.eq(token))
-            .filter(invitation::expires_at.gt(Utc::now().naive_utc()))
-            .filter(invitation::used_at.is_null())
-            .first::<Invitation>(connection)
-            .await
-            .optional()?;
+        let invitation = with_connection!(connection, |connection| {
+            invitation::table
+                .filter(invitation::token.eq(token))
+                .filter(invitation::expires_at.gt(Utc::now().naive_utc()))
+                .filter(invitation::used_at.is_null())
+                .first::<Invitation>(connection)
+                .await
+        })
+        .optional()?;
 
         Ok(invitation)
     }
 
+    /// パスワードを再設定
+    pub async fn update_password(
+        connection: &mut DbConnection,
+        user_id: &str,
+        password: &str,
+    ) -> Result<(), diesel::result::Error> {
+        let password_hash = hash_password(password).expect("パスワードのハッシュ化に失敗しました");
+
+        with_connection!(connection, |connection| {
+            diesel::update(user::table.filter(user::id.eq(user_id)))
+                .set(user::password_hash.eq(password_hash))
+                .execute(connection)
+                .await
+        })?;
+
+        Ok(())
+    }
+
     /// 招待を使用済みにマーク
     pub async fn mark_invitation_used(
-        connection: &mut AsyncMysqlConnection,
+        connection: &mut DbConnection,
         token: &str,
         used_by: &str,
     ) -> Result<(), diesel::result::Error> {
         let now = Utc::now().naive_utc();
 
-        diesel::update(invitation::table.filter(invitation::token.eq(token)))
-            .set((
-                invitation::used_at.eq(Some(now)),
-                invitation::used_by.eq(Some(used_by.to_string())),
-            ))
-            .execute(connection)
-            .await?;
+        with_connection!(connection, |connection| {
+            diesel::update(invitation::table.filter(invitation::token.eq(token)))
+                .set((
+                    invitation::used_at.eq(Some(now)),
+                    invitation::used_by.eq(Some(used_by.to_string())),
+                ))
+                .execute(connection)
+                .await
+        })?;
 
         Ok(())
     }
@@ -0,0 +1,46 @@
+use crate::database::repository::RepositoryResult;
+use crate::models::auth::{Invitation, User};
+
+pub mod memory;
+pub mod sql;
+
+pub use memory::InMemoryAuthRepository;
+pub use sql::SqlAuthRepository;
+
+/// Storage of users and invitations, used by the login backend and the handlers.
+#[async_trait::async_trait]
+pub trait AuthRepository: Send + Sync {
+    /// Whether any active user exists.
+    async fn has_any_users(&self) -> RepositoryResult<bool>;
+
+    /// Invitation without an inviter. Whoever registers with it becomes an administrator.
+    async fn create_system_invitation(&self, valid_hours: u32) -> RepositoryResult<Invitation>;
+
+    async fn create_invitation(
+        &self,
+        email: Option<&str>,
+        invited_by: Option<&str>,
+        valid_hours: u32,
+    ) -> RepositoryResult<Invitation>;
+
+    /// The invitation, if it is neither expired nor used.
+    async fn get_invitation_by_token(&self, token: &str) -> RepositoryResult<Option<Invitation>>;
+
+    async fn mark_invitation_used(&self, token: &str, used_by: &str) -> RepositoryResult<()>;
+
+    async fn get_user_by_username(&self, username: &str) -> RepositoryResult<Option<User>>;
+
+    async fn get_user_by_id(&self, user_id: &str) -> RepositoryResult<Option<User>>;
+
+    /// Creates an active user, hashing the password.
+    async fn create_user(
+        &self,
+        username: &str,
+        email: Option<&str>,
+        password: &str,
+        invited_by: Option<&str>,
+        is_admin: bool,
+    ) -> RepositoryResult<User>;
+
+    async fn update_password(&self, user_id: &str, password: &str) -> RepositoryResult<()>;
+}
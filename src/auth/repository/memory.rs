@@ -0,0 +1,159 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{Duration, Utc};
+use diesel::result::DatabaseErrorKind;
+use uuid::Uuid;
+
+use crate::auth::repository::AuthRepository;
+use crate::auth::utils::hash_password;
+use crate::database::repository::{RepositoryError, RepositoryResult};
+use crate::models::auth::{Invitation, User};
+
+/// User storage kept in memory, for tests and trying the app without a database.
+#[derive(Clone, Default)]
+pub struct InMemoryAuthRepository {
+    users: Arc<Mutex<Vec<User>>>,
+    invitations: Arc<Mutex<Vec<Invitation>>>,
+}
+
+impl InMemoryAuthRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn users(&self) -> MutexGuard<'_, Vec<User>> {
+        self.users.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn invitations(&self) -> MutexGuard<'_, Vec<Invitation>> {
+        self.invitations.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn find_active_user(&self, predicate: impl Fn(&User) -> bool) -> Option<User> {
+        self.users()
+            .iter()
+            .find(|user| user.is_active == Some(true) && predicate(user))
+            .cloned()
+    }
+
+    fn push_invitation(
+        &self,
+        email: Option<&str>,
+        invited_by: Option<String>,
+        valid_hours: u32,
+    ) -> Invitation {
+        let now = Utc::now().naive_utc();
+        let invitation = Invitation {
+            id: Uuid::new_v4().to_string(),
+            email: email.map(|s| s.to_string()),
+            token: Uuid::new_v4().to_string(),
+            invited_by,
+            expires_at: now + Duration::hours(valid_hours.into()),
+            used_at: None,
+            used_by: None,
+            created_at: Some(now),
+        };
+        self.invitations().push(invitation.clone());
+        invitation
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthRepository for InMemoryAuthRepository {
+    async fn has_any_users(&self) -> RepositoryResult<bool> {
+        Ok(self.find_active_user(|_| true).is_some())
+    }
+
+    async fn create_system_invitation(&self, valid_hours: u32) -> RepositoryResult<Invitation> {
+        Ok(self.push_invitation(None, None, valid_hours))
+    }
+
+    async fn create_invitation(
+        &self,
+        email: Option<&str>,
+        invited_by: Option<&str>,
+        valid_hours: u32,
+    ) -> RepositoryResult<Invitation> {
+        // SQL 版と同じく、招待者が不明な場合は anonymous とする
+        let invited_by = invited_by
+            .filter(|s| !s.is_empty())
+            .unwrap_or("anonymous")
+            .to_string();
+        Ok(self.push_invitation(email, Some(invited_by), valid_hours))
+    }
+
+    async fn get_invitation_by_token(&self, token: &str) -> RepositoryResult<Option<Invitation>> {
+        let now = Utc::now().naive_utc();
+        Ok(self
+            .invitations()
+            .iter()
+            .find(|invitation| {
+                invitation.token == token
+                    && invitation.expires_at > now
+                    && invitation.used_at.is_none()
+            })
+            .cloned())
+    }
+
+    async fn mark_invitation_used(&self, token: &str, used_by: &str) -> RepositoryResult<()> {
+        let now = Utc::now().naive_utc();
+        for invitation in self
+            .invitations()
+            .iter_mut()
+            .filter(|invitation| invitation.token == token)
+        {
+            invitation.used_at = Some(now);
+            invitation.used_by = Some(used_by.to_string());
+        }
+        Ok(())
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> RepositoryResult<Option<User>> {
+        Ok(self.find_active_user(|user| user.username == username))
+    }
+
+    async fn get_user_by_id(&self, user_id: &str) -> RepositoryResult<Option<User>> {
+        Ok(self.find_active_user(|user| user.id == user_id))
+    }
+
+    async fn create_user(
+        &self,
+        username: &str,
+        email: Option<&str>,
+        password: &str,
+        invited_by: Option<&str>,
+        is_admin: bool,
+    ) -> RepositoryResult<User> {
+        let password_hash = hash_password(password).expect("パスワードのハッシュ化に失敗しました");
+
+        let mut users = self.users();
+        if users.iter().any(|user| user.username == username) {
+            return Err(RepositoryError::database(
+                DatabaseErrorKind::UniqueViolation,
+                format!("Duplicate entry '{}' for key 'username'", username),
+            ));
+        }
+
+        let user = User {
+            id: Uuid::new_v4().to_string(),
+            username: username.to_string(),
+            email: email.map(|s| s.to_string()),
+            password_hash,
+            is_admin: Some(is_admin),
+            is_active: Some(true),
+            created_at: Some(Utc::now().naive_utc()),
+            invited_by: invited_by.map(|s| s.to_string()),
+        };
+        users.push(user.clone());
+        Ok(user)
+    }
+
+    async fn update_password(&self, user_id: &str, password: &str) -> RepositoryResult<()> {
+        let password_hash = hash_password(password).expect("パスワードのハッシュ化に失敗しました");
+
+        if let Some(user) = self.users().iter_mut().find(|user| user.id == user_id) {
+            user.password_hash = password_hash;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use crate::auth::queries::AuthQueries;
+use crate::auth::repository::AuthRepository;
+use crate::database::connection::DieselPool;
+use crate::database::repository::RepositoryResult;
+use crate::models::auth::{Invitation, User};
+
+/// User storage in the database selected by `DATABASE_URL`.
+#[derive(Clone)]
+pub struct SqlAuthRepository {
+    pool: DieselPool,
+}
+
+impl SqlAuthRepository {
+    pub fn new(pool: DieselPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthRepository for SqlAuthRepository {
+    async fn has_any_users(&self) -> RepositoryResult<bool> {
+        let mut connection = self.pool.get().await?;
+        Ok(AuthQueries::has_any_users(&mut connection).await?)
+    }
+
+    async fn create_system_invitation(&self, valid_hours: u32) -> RepositoryResult<Invitation> {
+        let mut connection = self.pool.get().await?;
+        Ok(AuthQueries::create_system_invitation(&mut connection, valid_hours).await?)
+    }
+
+    async fn create_invitation(
+        &self,
+        email: Option<&str>,
+        invited_by: Option<&str>,
+        valid_hours: u32,
+    ) -> RepositoryResult<Invitation> {
+        let mut connection = self.pool.get().await?;
+        Ok(AuthQueries::create_invitation(&mut connection, email, invited_by, valid_hours).await?)
+    }
+
+    async fn get_invitation_by_token(&self, token: &str) -> RepositoryResult<Option<Invitation>> {
+        let mut connection = self.pool.get().await?;
+        Ok(AuthQueries::get_invitation_by_token(&mut connection, token).await?)
+    }
+
+    async fn mark_invitation_used(&self, token: &str, used_by: &str) -> RepositoryResult<()> {
+        let mut connection = self.pool.get().await?;
+        Ok(AuthQueries::mark_invitation_used(&mut connection, token, used_by).await?)
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> RepositoryResult<Option<User>> {
+        let mut connection = self.pool.get().await?;
+        Ok(AuthQueries::get_user_by_username(&mut connection, username).await?)
+    }
+
+    async fn get_user_by_id(&self, user_id: &str) -> RepositoryResult<Option<User>> {
+        let mut connection = self.pool.get().await?;
+        Ok(AuthQueries::get_user_by_id(&mut connection, user_id).await?)
+    }
+
+    async fn create_user(
+        &self,
+        username: &str,
+        email: Option<&str>,
+        password: &str,
+        invited_by: Option<&str>,
+        is_admin: bool,
+    ) -> RepositoryResult<User> {
+        let mut connection = self.pool.get().await?;
+        Ok(AuthQueries::create_user(
+            &mut connection,
+            username,
+            email,
+            password,
+            invited_by,
+            is_admin,
+        )
+        .await?)
+    }
+
+    async fn update_password(&self, user_id: &str, password: &str) -> RepositoryResult<()> {
+        let mut connection = self.pool.get().await?;
+        Ok(AuthQueries::update_password(&mut connection, user_id, password).await?)
+    }
+}
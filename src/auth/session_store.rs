@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::models::sessions::{NewSession, Session};
 use crate::schema::sessions;
@@ -6,12 +8,67 @@ use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
 use tower_sessions::{
-    SessionStore,
+    ExpiredDeletion, SessionStore,
     session::{Id, Record},
     session_store,
 };
 
 use crate::auth::backend::DbPool;
+use crate::database::connection::with_connection;
+
+/// Seconds during which the activity of a session is not recorded again.
+pub const ACTIVITY_RECORD_INTERVAL_SECONDS: i64 = 60;
+/// Length of the `user_agent` column.
+const USER_AGENT_MAX_LENGTH: usize = 512;
+
+/// Sessions whose activity this process recorded recently, so that the following requests of
+/// the interval do not touch the database at all.
+#[derive(Clone, Default)]
+pub struct RecentActivity {
+    recorded: Arc<Mutex<HashMap<Id, Instant>>>,
+}
+
+impl RecentActivity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the activity of the session is due to be recorded. Answers `true` at most once
+    /// per [`ACTIVITY_RECORD_INTERVAL_SECONDS`] for each session.
+    pub fn is_due(&self, id: &Id) -> bool {
+        self.is_due_at(id, Instant::now())
+    }
+
+    fn is_due_at(&self, id: &Id, now: Instant) -> bool {
+        let interval = Duration::from_secs(ACTIVITY_RECORD_INTERVAL_SECONDS as u64);
+        let mut recorded = self.recorded.lock().unwrap_or_else(|e| e.into_inner());
+        // 間隔を過ぎた記録は不要なので、ここで捨てて大きくならないようにする
+        recorded.retain(|_, at| now.saturating_duration_since(*at) < interval);
+        if recorded.contains_key(id) {
+            return false;
+        }
+        recorded.insert(*id, now);
+        true
+    }
+}
+
+/// The purge task started by [`DieselSessionStore::spawn_expired_deletion`], aborted when dropped.
+pub struct ExpiredDeletionTask(tokio::task::JoinHandle<()>);
+
+impl ExpiredDeletionTask {
+    /// Aborts the task and waits until it has stopped, e.g. before the pool is closed.
+    pub async fn stop(mut self) {
+        self.0.abort();
+        // 中断による JoinError は想定どおりなので捨てる
+        let _ = (&mut self.0).await;
+    }
+}
+
+impl Drop for ExpiredDeletionTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
 
 /// A session store implementation using Diesel ORM for database interactions.
 #[derive(Clone)]
@@ -33,6 +90,172 @@ impl DieselSessionStore {
     pub fn new(pool: DbPool) -> Self {
         Self { pool }
     }
+
+    /// Deletes sessions whose expiry date has passed and returns how many were removed.
+    pub async fn delete_expired_sessions(&self) -> session_store::Result<usize> {
+        let mut connection = self.pool.get().await.map_err(|e| {
+            session_store::Error::Backend(format!("Failed to get connection: {}", e))
+        })?;
+
+        with_connection!(&mut connection, |connection| {
+            diesel::delete(sessions::table)
+                .filter(sessions::expiry_date.lt(chrono::Utc::now().naive_utc()))
+                .execute(connection)
+                .await
+        })
+        .map_err(|e| session_store::Error::Backend(format!("Failed to delete sessions: {}", e)))
+    }
+
+    /// Purges expired sessions every `period` until the returned task is stopped or dropped.
+    ///
+    /// Unlike `continuously_delete_expired` of tower-sessions, a failed purge is logged and retried
+    /// at the next tick instead of ending the task.
+    pub fn spawn_expired_deletion(self, period: std::time::Duration) -> ExpiredDeletionTask {
+        ExpiredDeletionTask(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            // 最初の tick はすぐに完了するため読み飛ばす
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.delete_expired().await {
+                    tracing::warn!(error = %e, "Failed to delete expired sessions");
+                }
+            }
+        }))
+    }
+
+    /// Number of sessions that have not expired yet.
+    pub async fn count_active_sessions(&self) -> session_store::Result<i64> {
+        let mut connection = self.pool.get().await.map_err(|e| {
+            session_store::Error::Backend(format!("Failed to get connection: {}", e))
+        })?;
+
+        with_connection!(&mut connection, |connection| {
+            sessions::table
+                .filter(sessions::expiry_date.ge(chrono::Utc::now().naive_utc()))
+                .count()
+                .get_result(connection)
+                .await
+        })
+        .map_err(|e| session_store::Error::Backend(format!("Failed to count sessions: {}", e)))
+    }
+
+    /// Records who uses a session and from where.
+    ///
+    /// Written at most once per [`ACTIVITY_RECORD_INTERVAL_SECONDS`] per session, so that every
+    /// request does not cost a write.
+    pub async fn record_activity(
+        &self,
+        id: &Id,
+        user_id: &str,
+        user_agent: Option<&str>,
+        ip_address: &str,
+    ) -> session_store::Result<()> {
+        let mut connection = self.pool.get().await.map_err(|e| {
+            session_store::Error::Backend(format!("Failed to get connection: {}", e))
+        })?;
+
+        let now = chrono::Utc::now().naive_utc();
+        let threshold = now - chrono::Duration::seconds(ACTIVITY_RECORD_INTERVAL_SECONDS);
+        // 列の長さを超える User-Agent は切り詰める
+        let user_agent = user_agent.map(|agent| {
+            agent
+                .chars()
+                .take(USER_AGENT_MAX_LENGTH)
+                .collect::<String>()
+        });
+
+        with_connection!(&mut connection, |connection| {
+            diesel::update(
+                sessions::table
+                    .filter(sessions::id.eq(id.to_string()))
+                    .filter(
+                        sessions::last_seen_at
+                            .is_null()
+                            .or(sessions::last_seen_at.lt(threshold)),
+                    ),
+            )
+            .set((
+                sessions::user_id.eq(user_id),
+                sessions::user_agent.eq(&user_agent),
+                sessions::ip_address.eq(ip_address),
+                sessions::last_seen_at.eq(now),
+            ))
+            .execute(connection)
+            .await
+        })
+        .map_err(|e| session_store::Error::Backend(format!("Failed to record activity: {}", e)))?;
+        Ok(())
+    }
+
+    /// Sessions of a user that have not expired, most recently used first.
+    pub async fn list_user_sessions(&self, user_id: &str) -> session_store::Result<Vec<Session>> {
+        let mut connection = self.pool.get().await.map_err(|e| {
+            session_store::Error::Backend(format!("Failed to get connection: {}", e))
+        })?;
+
+        with_connection!(&mut connection, |connection| {
+            sessions::table
+                .filter(sessions::user_id.eq(user_id))
+                .filter(sessions::expiry_date.ge(chrono::Utc::now().naive_utc()))
+                .order((sessions::last_seen_at.desc(), sessions::expiry_date.desc()))
+                .load::<Session>(connection)
+                .await
+        })
+        .map_err(|e| session_store::Error::Backend(format!("Failed to list sessions: {}", e)))
+    }
+
+    /// Deletes a session if it belongs to the user, logging that device out.
+    pub async fn delete_user_session(
+        &self,
+        user_id: &str,
+        session_id: &str,
+    ) -> session_store::Result<usize> {
+        let mut connection = self.pool.get().await.map_err(|e| {
+            session_store::Error::Backend(format!("Failed to get connection: {}", e))
+        })?;
+
+        with_connection!(&mut connection, |connection| {
+            diesel::delete(sessions::table)
+                .filter(sessions::id.eq(session_id))
+                .filter(sessions::user_id.eq(user_id))
+                .execute(connection)
+                .await
+        })
+        .map_err(|e| session_store::Error::Backend(format!("Failed to delete session: {}", e)))
+    }
+
+    /// Deletes every session of the user except `keep_id`.
+    pub async fn delete_other_user_sessions(
+        &self,
+        user_id: &str,
+        keep_id: &str,
+    ) -> session_store::Result<usize> {
+        let mut connection = self.pool.get().await.map_err(|e| {
+            session_store::Error::Backend(format!("Failed to get connection: {}", e))
+        })?;
+
+        with_connection!(&mut connection, |connection| {
+            diesel::delete(sessions::table)
+                .filter(sessions::user_id.eq(user_id))
+                .filter(sessions::id.ne(keep_id))
+                .execute(connection)
+                .await
+        })
+        .map_err(|e| session_store::Error::Backend(format!("Failed to delete sessions: {}", e)))
+    }
+
+    /// Deletes every session, logging out all users.
+    pub async fn delete_all_sessions(&self) -> session_store::Result<usize> {
+        let mut connection = self.pool.get().await.map_err(|e| {
+            session_store::Error::Backend(format!("Failed to get connection: {}", e))
+        })?;
+
+        with_connection!(&mut connection, |connection| {
+            diesel::delete(sessions::table).execute(connection).await
+        })
+        .map_err(|e| session_store::Error::Backend(format!("Failed to delete sessions: {}", e)))
+    }
 }
 
 #[async_trait::async_trait]
@@ -50,10 +273,13 @@ impl SessionStore for DieselSessionStore {
             let session_id_str = record.id.to_string();
 
             // To check if the session ID already exists
-            let existing_session: Option<Session> = sessions::table
-                .filter(sessions::id.eq(&session_id_str))
-                .first(&mut connection)
-                .await
+            let existing_session: Option<Session> =
+                with_connection!(&mut connection, |connection| {
+                    sessions::table
+                        .filter(sessions::id.eq(&session_id_str))
+                        .first(connection)
+                        .await
+                })
                 .optional()
                 .map_err(|e| {
                     session_store::Error::Backend(format!("Failed to check session: {}", e))
@@ -66,13 +292,15 @@ impl SessionStore for DieselSessionStore {
                     expiry_date: expiry_naive,
                 };
 
-                diesel::insert_into(sessions::table)
-                    .values(new_session)
-                    .execute(&mut connection)
-                    .await
-                    .map_err(|e| {
-                        session_store::Error::Backend(format!("Failed to create session: {}", e))
-                    })?;
+                with_connection!(&mut connection, |connection| {
+                    diesel::insert_into(sessions::table)
+                        .values(new_session)
+                        .execute(connection)
+                        .await
+                })
+                .map_err(|e| {
+                    session_store::Error::Backend(format!("Failed to create session: {}", e))
+                })?;
 
                 return Ok(());
             } else {
@@ -92,27 +320,29 @@ impl SessionStore for DieselSessionStore {
         let expiry_naive = offset_to_naive_datetime(record.expiry_date);
 
         // To check if the session already exists
-        let existing_session: Option<Session> = sessions::table
-            .filter(sessions::id.eq(&session_id_str))
-            .first(&mut connection)
-            .await
-            .optional()
-            .map_err(|e| {
-                session_store::Error::Backend(format!("Failed to check session: {}", e))
-            })?;
+        let existing_session: Option<Session> = with_connection!(&mut connection, |connection| {
+            sessions::table
+                .filter(sessions::id.eq(&session_id_str))
+                .first(connection)
+                .await
+        })
+        .optional()
+        .map_err(|e| session_store::Error::Backend(format!("Failed to check session: {}", e)))?;
 
         // Need to change operation based on whether the session exists
         if existing_session.is_some() {
-            diesel::update(sessions::table.filter(sessions::id.eq(&session_id_str)))
-                .set((
-                    sessions::data.eq(data_str),
-                    sessions::expiry_date.eq(expiry_naive),
-                ))
-                .execute(&mut connection)
-                .await
-                .map_err(|e| {
-                    session_store::Error::Backend(format!("Failed to update session: {}", e))
-                })?;
+            with_connection!(&mut connection, |connection| {
+                diesel::update(sessions::table.filter(sessions::id.eq(&session_id_str)))
+                    .set((
+                        sessions::data.eq(data_str),
+                        sessions::expiry_date.eq(expiry_naive),
+                    ))
+                    .execute(connection)
+                    .await
+            })
+            .map_err(|e| {
+                session_store::Error::Backend(format!("Failed to update session: {}", e))
+            })?;
         } else {
             let new_session = NewSession {
                 id: session_id_str,
@@ -120,13 +350,15 @@ impl SessionStore for DieselSessionStore {
                 expiry_date: expiry_naive,
             };
 
-            diesel::insert_into(sessions::table)
-                .values(new_session)
-                .execute(&mut connection)
-                .await
-                .map_err(|e| {
-                    session_store::Error::Backend(format!("Failed to create session: {}", e))
-                })?;
+            with_connection!(&mut connection, |connection| {
+                diesel::insert_into(sessions::table)
+                    .values(new_session)
+                    .execute(connection)
+                    .await
+            })
+            .map_err(|e| {
+                session_store::Error::Backend(format!("Failed to create session: {}", e))
+            })?;
         }
 
         Ok(())
@@ -140,12 +372,14 @@ impl SessionStore for DieselSessionStore {
         let session_id_str = id.to_string();
 
         // Need to check if the session exists with given ID
-        let session: Option<Session> = sessions::table
-            .filter(sessions::id.eq(&session_id_str))
-            .first(&mut connection)
-            .await
-            .optional()
-            .map_err(|e| session_store::Error::Backend(format!("Failed to load session: {}", e)))?;
+        let session: Option<Session> = with_connection!(&mut connection, |connection| {
+            sessions::table
+                .filter(sessions::id.eq(&session_id_str))
+                .first(connection)
+                .await
+        })
+        .optional()
+        .map_err(|e| session_store::Error::Backend(format!("Failed to load session: {}", e)))?;
 
         match session {
             Some(session) => {
@@ -177,14 +411,23 @@ impl SessionStore for DieselSessionStore {
 
         let session_id_str = id.to_string();
 
-        diesel::delete(sessions::table)
-            .filter(sessions::id.eq(session_id_str))
-            .execute(&mut connection)
-            .await
-            .map_err(|e| {
-                session_store::Error::Backend(format!("Failed to delete session: {}", e))
-            })?;
+        with_connection!(&mut connection, |connection| {
+            diesel::delete(sessions::table)
+                .filter(sessions::id.eq(session_id_str))
+                .execute(connection)
+                .await
+        })
+        .map_err(|e| session_store::Error::Backend(format!("Failed to delete session: {}", e)))?;
+
+        Ok(())
+    }
+}
 
+#[async_trait::async_trait]
+impl ExpiredDeletion for DieselSessionStore {
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        let deleted = self.delete_expired_sessions().await?;
+        tracing::debug!(deleted, "Deleted expired sessions");
         Ok(())
     }
 }
@@ -221,3 +464,25 @@ fn offset_to_naive_datetime(offset: time::OffsetDateTime) -> NaiveDateTime {
         .unwrap_or(chrono::DateTime::UNIX_EPOCH)
         .naive_utc()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn activity_is_due_once_per_interval() {
+        let activity = RecentActivity::new();
+        let (first, second) = (Id::default(), Id::default());
+        let start = Instant::now();
+        let interval = Duration::from_secs(ACTIVITY_RECORD_INTERVAL_SECONDS as u64);
+
+        assert!(activity.is_due_at(&first, start));
+        assert!(!activity.is_due_at(&first, start + interval / 2));
+        assert!(activity.is_due_at(&second, start + interval / 2));
+        assert!(activity.is_due_at(&first, start + interval));
+
+        // 間隔を過ぎたセッションは覚えておかない
+        activity.is_due_at(&Id::default(), start + interval * 3);
+        assert_eq!(activity.recorded.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::auth::utils::{
+    LOGIN_ATTEMPT_WINDOW_MINUTES, LOGIN_FREE_ATTEMPTS, LOGIN_LOCKOUT_BASE_SECONDS,
+    LOGIN_LOCKOUT_MAX_SECONDS, LOGIN_THROTTLE_MAX_KEYS,
+};
+
+/// What failed login attempts are counted against: one username tried from one client address.
+///
+/// Locking a username everywhere would let anyone lock its owner out, and locking an address
+/// would lock out everyone behind the same proxy or NAT.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ThrottleKey {
+    pub ip: IpAddr,
+    /// Compared case-insensitively so that `Admin` and `admin` share a counter
+    pub username: String,
+}
+
+impl ThrottleKey {
+    pub fn new(ip: IpAddr, username: &str) -> Self {
+        Self {
+            ip,
+            username: username.trim().to_lowercase(),
+        }
+    }
+
+    /// Rebuilds a key from the values posted by the admin page.
+    pub fn from_parts(ip: &str, username: &str) -> Option<Self> {
+        ip.parse().ok().map(|ip| ThrottleKey::new(ip, username))
+    }
+}
+
+impl std::fmt::Display for ThrottleKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "IP {} のユーザー名 {}", self.ip, self.username)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AttemptRecord {
+    failures: u32,
+    last_failure: DateTime<Utc>,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+/// Outcome of [`LoginThrottle::begin_attempt`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attempt {
+    /// A key is locked until then; the password must not be checked.
+    Refused(DateTime<Utc>),
+    /// The attempt was counted as a failure up front, so concurrent attempts cannot all get past
+    /// the lockout while their passwords are being checked. Holds the lockout this attempt
+    /// started, which stays in place unless the login succeeds.
+    Counted(Option<DateTime<Utc>>),
+}
+
+/// A snapshot of a currently locked key, for the admin page.
+#[derive(Debug, Clone)]
+pub struct LockoutEntry {
+    pub key: ThrottleKey,
+    pub failures: u32,
+    pub last_failure: DateTime<Utc>,
+    pub locked_until: DateTime<Utc>,
+}
+
+/// In-memory tracker of failed login attempts per client address and username.
+///
+/// After `LOGIN_FREE_ATTEMPTS` failures the key is locked, and every further failure
+/// doubles the lockout (capped at `LOGIN_LOCKOUT_MAX_SECONDS`).
+/// Counters are forgotten once no failure happened for `LOGIN_ATTEMPT_WINDOW_MINUTES`.
+/// At most `LOGIN_THROTTLE_MAX_KEYS` keys are tracked; beyond that the oldest unlocked one,
+/// or failing that the oldest one, is dropped.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    records: Arc<Mutex<HashMap<ThrottleKey, AttemptRecord>>>,
+    max_keys: usize,
+}
+
+impl Default for LoginThrottle {
+    fn default() -> Self {
+        Self {
+            records: Arc::default(),
+            max_keys: LOGIN_THROTTLE_MAX_KEYS,
+        }
+    }
+}
+
+impl LoginThrottle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a login attempt against the key before the password is checked.
+    ///
+    /// Refuses it without counting while the key is locked.
+    pub fn begin_attempt(&self, key: &ThrottleKey) -> Attempt {
+        self.begin_attempt_at(key, Utc::now())
+    }
+
+    fn begin_attempt_at(&self, key: &ThrottleKey, now: DateTime<Utc>) -> Attempt {
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        prune(&mut records, now);
+
+        if let Some(locked_until) = records
+            .get(key)
+            .and_then(|record| record.locked_until)
+            .filter(|locked_until| *locked_until > now)
+        {
+            return Attempt::Refused(locked_until);
+        }
+
+        if !records.contains_key(key) && records.len() >= self.max_keys {
+            evict_oldest(&mut records, now);
+        }
+        let record = records.entry(key.clone()).or_insert(AttemptRecord {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+        record.failures += 1;
+        record.last_failure = now;
+
+        let Some(duration) = lockout_duration(record.failures) else {
+            return Attempt::Counted(None);
+        };
+        let locked_until = now + duration;
+        record.locked_until = Some(locked_until);
+        tracing::warn!(
+            key = %key,
+            failures = record.failures,
+            locked_until = %locked_until,
+            "Login locked after repeated failures"
+        );
+        Attempt::Counted(Some(locked_until))
+    }
+
+    /// Clears the counter after a successful login, including the attempt that just succeeded.
+    pub fn record_success(&self, key: &ThrottleKey) {
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        records.remove(key);
+    }
+
+    /// Lists the keys that are locked right now, latest lockout first.
+    pub fn active_lockouts(&self) -> Vec<LockoutEntry> {
+        let now = Utc::now();
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        prune(&mut records, now);
+
+        let mut entries: Vec<LockoutEntry> = records
+            .iter()
+            .filter_map(|(key, record)| {
+                let locked_until = record.locked_until.filter(|until| *until > now)?;
+                Some(LockoutEntry {
+                    key: key.clone(),
+                    failures: record.failures,
+                    last_failure: record.last_failure,
+                    locked_until,
+                })
+            })
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.locked_until));
+        entries
+    }
+
+    /// Lifts the lockout of a key by hand (used from the admin page).
+    pub fn unlock(&self, key: &ThrottleKey) -> bool {
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        records.remove(key).is_some()
+    }
+}
+
+/// Lockout to apply after the given number of consecutive failures.
+fn lockout_duration(failures: u32) -> Option<Duration> {
+    if failures < LOGIN_FREE_ATTEMPTS {
+        return None;
+    }
+    let exponent = (failures - LOGIN_FREE_ATTEMPTS).min(16);
+    let seconds = LOGIN_LOCKOUT_BASE_SECONDS
+        .saturating_mul(1 << exponent)
+        .min(LOGIN_LOCKOUT_MAX_SECONDS);
+    Some(Duration::seconds(seconds))
+}
+
+/// Drops records whose lockout has ended and that saw no failure within the window.
+fn prune(records: &mut HashMap<ThrottleKey, AttemptRecord>, now: DateTime<Utc>) {
+    let window = Duration::minutes(LOGIN_ATTEMPT_WINDOW_MINUTES);
+    records.retain(|_, record| {
+        let still_locked = record.locked_until.is_some_and(|until| until > now);
+        still_locked || now - record.last_failure < window
+    });
+}
+
+/// Makes room for a new key, preferring to forget a counter over lifting a lockout.
+fn evict_oldest(records: &mut HashMap<ThrottleKey, AttemptRecord>, now: DateTime<Utc>) {
+    let oldest = records
+        .iter()
+        .min_by_key(|(_, record)| {
+            let locked = record.locked_until.is_some_and(|until| until > now);
+            (locked, record.last_failure)
+        })
+        .map(|(key, _)| key.clone());
+    if let Some(key) = oldest {
+        records.remove(&key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> ThrottleKey {
+        ThrottleKey::new("192.0.2.1".parse().unwrap(), "Alice")
+    }
+
+    fn start() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn first_failures_are_free() {
+        let throttle = LoginThrottle::new();
+        let now = start();
+
+        for _ in 1..LOGIN_FREE_ATTEMPTS {
+            assert_eq!(
+                throttle.begin_attempt_at(&key(), now),
+                Attempt::Counted(None)
+            );
+        }
+
+        let locked_until = now + Duration::seconds(30);
+        assert_eq!(LOGIN_FREE_ATTEMPTS, 5);
+        assert_eq!(
+            throttle.begin_attempt_at(&key(), now),
+            Attempt::Counted(Some(locked_until))
+        );
+        assert_eq!(
+            throttle.begin_attempt_at(&key(), now),
+            Attempt::Refused(locked_until)
+        );
+    }
+
+    #[test]
+    fn attempts_in_flight_count_towards_the_lockout() {
+        let throttle = LoginThrottle::new();
+        let now = start();
+
+        // 結果を待たずに送られた試行も、パスワードの検証前に数えられる
+        let attempts: Vec<Attempt> = (0..LOGIN_FREE_ATTEMPTS + 3)
+            .map(|_| throttle.begin_attempt_at(&key(), now))
+            .collect();
+        let checked = attempts
+            .iter()
+            .filter(|attempt| matches!(attempt, Attempt::Counted(_)))
+            .count();
+        assert_eq!(checked, LOGIN_FREE_ATTEMPTS as usize);
+    }
+
+    #[test]
+    fn lockout_starts_at_30_seconds_and_doubles() {
+        assert_eq!(lockout_duration(4), None);
+        assert_eq!(lockout_duration(5), Some(Duration::seconds(30)));
+        assert_eq!(lockout_duration(6), Some(Duration::seconds(60)));
+        assert_eq!(lockout_duration(7), Some(Duration::seconds(120)));
+        assert_eq!(lockout_duration(10), Some(Duration::seconds(960)));
+    }
+
+    #[test]
+    fn lockout_is_capped_at_one_hour() {
+        assert_eq!(lockout_duration(12), Some(Duration::seconds(3600)));
+        assert_eq!(lockout_duration(40), Some(Duration::seconds(3600)));
+        assert_eq!(lockout_duration(u32::MAX), Some(Duration::seconds(3600)));
+    }
+
+    #[test]
+    fn lockout_expires() {
+        let throttle = LoginThrottle::new();
+        let now = start();
+        for _ in 0..LOGIN_FREE_ATTEMPTS {
+            throttle.begin_attempt_at(&key(), now);
+        }
+
+        assert!(matches!(
+            throttle.begin_attempt_at(&key(), now + Duration::seconds(29)),
+            Attempt::Refused(_)
+        ));
+        assert!(matches!(
+            throttle.begin_attempt_at(&key(), now + Duration::seconds(30)),
+            Attempt::Counted(_)
+        ));
+    }
+
+    #[test]
+    fn counters_reset_after_the_window() {
+        let almost_locked = || {
+            let throttle = LoginThrottle::new();
+            for _ in 1..LOGIN_FREE_ATTEMPTS {
+                throttle.begin_attempt_at(&key(), start());
+            }
+            throttle
+        };
+
+        let within = start() + Duration::minutes(LOGIN_ATTEMPT_WINDOW_MINUTES - 1);
+        assert!(matches!(
+            almost_locked().begin_attempt_at(&key(), within),
+            Attempt::Counted(Some(_))
+        ));
+
+        let after = start() + Duration::minutes(LOGIN_ATTEMPT_WINDOW_MINUTES);
+        assert_eq!(LOGIN_ATTEMPT_WINDOW_MINUTES, 60);
+        assert_eq!(
+            almost_locked().begin_attempt_at(&key(), after),
+            Attempt::Counted(None)
+        );
+    }
+
+    #[test]
+    fn success_clears_the_counters() {
+        let throttle = LoginThrottle::new();
+        let now = start();
+        for _ in 0..LOGIN_FREE_ATTEMPTS {
+            throttle.begin_attempt_at(&key(), now);
+        }
+        assert!(matches!(
+            throttle.begin_attempt_at(&key(), now),
+            Attempt::Refused(_)
+        ));
+
+        throttle.record_success(&key());
+
+        for _ in 1..LOGIN_FREE_ATTEMPTS {
+            assert_eq!(
+                throttle.begin_attempt_at(&key(), now),
+                Attempt::Counted(None)
+            );
+        }
+    }
+
+    #[test]
+    fn usernames_share_a_counter_regardless_of_case() {
+        let ip = "192.0.2.1".parse().unwrap();
+        assert_eq!(
+            ThrottleKey::new(ip, " Admin "),
+            ThrottleKey::new(ip, "admin")
+        );
+        assert_eq!(
+            ThrottleKey::from_parts("192.0.2.1", "ADMIN"),
+            Some(ThrottleKey::new(ip, "admin"))
+        );
+        assert_eq!(ThrottleKey::from_parts("not an address", "admin"), None);
+    }
+
+    #[test]
+    fn lockouts_only_apply_to_the_same_address_and_username() {
+        let throttle = LoginThrottle::new();
+        let now = start();
+        for _ in 0..LOGIN_FREE_ATTEMPTS {
+            throttle.begin_attempt_at(&key(), now);
+        }
+
+        assert!(matches!(
+            throttle.begin_attempt_at(&key(), now),
+            Attempt::Refused(_)
+        ));
+        // 他の端末からの本人のログインや、同じ IP の他のユーザーは締め出さない
+        let other_ip = ThrottleKey::new("192.0.2.2".parse().unwrap(), "Alice");
+        let other_user = ThrottleKey::new("192.0.2.1".parse().unwrap(), "Bob");
+        assert_eq!(
+            throttle.begin_attempt_at(&other_ip, now),
+            Attempt::Counted(None)
+        );
+        assert_eq!(
+            throttle.begin_attempt_at(&other_user, now),
+            Attempt::Counted(None)
+        );
+    }
+
+    #[test]
+    fn tracked_keys_are_bounded_and_lockouts_kept_longest() {
+        let throttle = LoginThrottle {
+            max_keys: 20,
+            ..LoginThrottle::new()
+        };
+        let now = start();
+        for _ in 0..LOGIN_FREE_ATTEMPTS {
+            throttle.begin_attempt_at(&key(), now);
+        }
+        for i in 0..30 {
+            let other = ThrottleKey::new("198.51.100.1".parse().unwrap(), &format!("user{}", i));
+            throttle.begin_attempt_at(&other, now + Duration::seconds(1));
+        }
+
+        let records = throttle.records.lock().unwrap();
+        assert_eq!(records.len(), 20);
+        assert!(
+            records
+                .get(&key())
+                .is_some_and(|record| record.locked_until.is_some())
+        );
+    }
+}
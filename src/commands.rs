@@ -0,0 +1,246 @@
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+
+use crate::auth::queries::AuthQueries;
+use crate::auth::session_store::DieselSessionStore;
+use crate::auth::utils::{validate_password, validate_username};
+use crate::config::AppConfig;
+use crate::database::connection::{DieselPool, create_diesel_pool};
+use crate::database::migrations::run_pending_migrations;
+use crate::database::queries::{VeinWithStatus, import_vein, search_veins};
+use crate::models::forms::SearchQuery;
+
+/// Operations available from the command line. Without a subcommand the server is started.
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the web server (default)
+    Serve,
+    /// Apply pending database migrations
+    Migrate,
+    /// Create an administrator account
+    CreateAdmin {
+        username: String,
+        #[arg(long)]
+        email: Option<String>,
+        /// Read from standard input when omitted
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Issue an invitation link
+    IssueInvite {
+        #[arg(long)]
+        email: Option<String>,
+        /// The invited user becomes an administrator
+        #[arg(long)]
+        admin: bool,
+    },
+    /// Set a new password for a user
+    ResetPassword {
+        username: String,
+        /// Read from standard input when omitted
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Import veins from a JSON file written by `export`
+    Import { file: PathBuf },
+    /// Export all veins, including revoked ones, as JSON
+    Export {
+        /// Write to this file instead of standard output
+        file: Option<PathBuf>,
+    },
+    /// Delete expired login sessions
+    PurgeSessions {
+        /// Delete every session, logging out all users
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+/// Runs a management subcommand. `Serve` is handled by the binary itself.
+pub async fn run(command: Command, config: &AppConfig) -> Result<()> {
+    match command {
+        Command::Serve => anyhow::bail!("serve is handled by main"),
+        Command::Migrate => migrate(config).await,
+        Command::CreateAdmin {
+            username,
+            email,
+            password,
+        } => create_admin(config, &username, email.as_deref(), password).await,
+        Command::IssueInvite { email, admin } => {
+            issue_invite(config, email.as_deref(), admin).await
+        }
+        Command::ResetPassword { username, password } => {
+            reset_password(config, &username, password).await
+        }
+        Command::Import { file } => import(config, &file).await,
+        Command::Export { file } => export(config, file.as_ref()).await,
+        Command::PurgeSessions { all } => purge_sessions(config, all).await,
+    }
+}
+
+async fn migrate(config: &AppConfig) -> Result<()> {
+    let applied = run_pending_migrations(&config.database_url).await?;
+    if applied.is_empty() {
+        println!("Database schema is up to date");
+    }
+    for version in applied {
+        println!("Applied migration {}", version);
+    }
+    Ok(())
+}
+
+async fn create_admin(
+    config: &AppConfig,
+    username: &str,
+    email: Option<&str>,
+    password: Option<String>,
+) -> Result<()> {
+    validate_username(username).map_err(anyhow::Error::msg)?;
+    let password = password_or_prompt(password)?;
+    validate_password(&password).map_err(anyhow::Error::msg)?;
+
+    let pool = create_diesel_pool(config).await?;
+    let mut connection = pool.get().await?;
+
+    if AuthQueries::get_user_by_username(&mut connection, username)
+        .await?
+        .is_some()
+    {
+        anyhow::bail!("User {} already exists", username);
+    }
+
+    let user =
+        AuthQueries::create_user(&mut connection, username, email, &password, None, true).await?;
+    println!("Created administrator {} ({})", user.username, user.id);
+    Ok(())
+}
+
+async fn issue_invite(config: &AppConfig, email: Option<&str>, admin: bool) -> Result<()> {
+    let pool = create_diesel_pool(config).await?;
+    let mut connection = pool.get().await?;
+
+    // 招待者のいないシステム招待で登録したユーザーは管理者になる
+    let invitation = if admin {
+        AuthQueries::create_system_invitation(&mut connection, config.invitation_duration_hours)
+            .await?
+    } else {
+        AuthQueries::create_invitation(
+            &mut connection,
+            email,
+            None,
+            config.invitation_duration_hours,
+        )
+        .await?
+    };
+
+    println!("{}", config.invitation_url(&invitation.token));
+    println!(
+        "有効期限: {}",
+        invitation.expires_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    Ok(())
+}
+
+async fn reset_password(
+    config: &AppConfig,
+    username: &str,
+    password: Option<String>,
+) -> Result<()> {
+    let pool = create_diesel_pool(config).await?;
+    let mut connection = pool.get().await?;
+
+    let user = AuthQueries::get_user_by_username(&mut connection, username)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("User {} not found", username))?;
+
+    let password = password_or_prompt(password)?;
+    validate_password(&password).map_err(anyhow::Error::msg)?;
+
+    AuthQueries::update_password(&mut connection, &user.id, &password).await?;
+    println!("Password of {} has been reset", user.username);
+    Ok(())
+}
+
+async fn import(config: &AppConfig, file: &PathBuf) -> Result<()> {
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    let veins: Vec<VeinWithStatus> = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", file.display()))?;
+
+    let pool = create_diesel_pool(config).await?;
+    let mut connection = pool.get().await?;
+
+    let mut imported = 0;
+    let mut skipped = 0;
+    for vein in &veins {
+        match import_vein(&mut connection, vein).await {
+            Ok(()) => imported += 1,
+            // 同じIDの鉱脈が既に存在する場合は上書きせずに飛ばす
+            Err(diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            )) => {
+                println!("Skipping existing vein {} ({})", vein.name, vein.id);
+                skipped += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    println!("Imported {} veins, skipped {}", imported, skipped);
+    Ok(())
+}
+
+async fn export(config: &AppConfig, file: Option<&PathBuf>) -> Result<()> {
+    let pool = create_diesel_pool(config).await?;
+    let veins = all_veins(&pool).await?;
+    let json = serde_json::to_string_pretty(&veins)?;
+
+    match file {
+        Some(path) => {
+            std::fs::write(path, json)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!("Exported {} veins to {}", veins.len(), path.display());
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+async fn purge_sessions(config: &AppConfig, all: bool) -> Result<()> {
+    let pool = create_diesel_pool(config).await?;
+    let store = DieselSessionStore::new(pool);
+
+    let deleted = if all {
+        store.delete_all_sessions().await?
+    } else {
+        store.delete_expired_sessions().await?
+    };
+    println!("Deleted {} sessions", deleted);
+    Ok(())
+}
+
+async fn all_veins(pool: &DieselPool) -> Result<Vec<VeinWithStatus>> {
+    let mut connection = pool.get().await?;
+    let query = SearchQuery {
+        include_revoked: Some(true),
+        ..Default::default()
+    };
+    Ok(search_veins(&mut connection, &query).await?)
+}
+
+/// Uses the password given as a flag, or reads one line from standard input.
+fn password_or_prompt(password: Option<String>) -> Result<String> {
+    if let Some(password) = password {
+        return Ok(password);
+    }
+
+    eprint!("Password: ");
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
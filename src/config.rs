@@ -1,30 +1,263 @@
-use std::env;
-
-#[derive(Debug, Clone)]
-pub struct AppConfig {
-    pub port: String,
-    pub database_url: String,
-}
-
-impl AppConfig {
-    pub fn from_env() -> anyhow::Result<Self> {
-        dotenv::dotenv().ok();
-        
-        let port = env::var("PORT").unwrap_or_else(|_| {
-            println!("PORT environment variable not set, using default port 24528");
-            "24528".to_string()
-        });
-        
-        let database_url = env::var("DATABASE_URL")
-            .map_err(|_| anyhow::anyhow!("DATABASE_URL environment variable must be set"))?;
-        
-        Ok(AppConfig {
-            port,
-            database_url,
-        })
-    }
-    
-    pub fn server_address(&self) -> String {
-        format!("0.0.0.0:{}", self.port)
-    }
-}
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+
+use axum::http::HeaderMap;
+use clap::Args;
+use serde::Deserialize;
+
+use crate::logging::LogFormat;
+
+// 設定の既定値
+pub const DEFAULT_BIND_ADDRESS: &str = "0.0.0.0";
+pub const DEFAULT_PORT: u16 = 24528;
+pub const DEFAULT_DATABASE_POOL_SIZE: usize = 16;
+pub const DEFAULT_SESSION_DURATION_DAYS: i64 = 7;
+pub const DEFAULT_SESSION_CLEANUP_INTERVAL_MINUTES: u64 = 60;
+/// 8 days, as long as invitations lasted before the duration could be configured.
+pub const DEFAULT_INVITATION_DURATION_HOURS: u32 = 192;
+pub const DEFAULT_LOG_LEVEL: &str = "info";
+/// Read when `--config` / `GT6_CONFIG` is not given and the file exists.
+pub const DEFAULT_CONFIG_FILE: &str = "gt6-vein-manager.toml";
+
+/// Settings given on the command line or through the environment.
+///
+/// Precedence is: command-line flag > environment variable > config file > default.
+#[derive(Debug, Default, Clone, Args)]
+pub struct ConfigArgs {
+    /// Path to a TOML config file
+    #[arg(long, env = "GT6_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Address to listen on
+    #[arg(long, env = "BIND_ADDRESS")]
+    pub bind_address: Option<String>,
+
+    /// Port to listen on
+    #[arg(long, env = "PORT")]
+    pub port: Option<u16>,
+
+    /// URL under which users reach the server, used in invitation links
+    #[arg(long, env = "PUBLIC_URL")]
+    pub public_url: Option<String>,
+
+    /// mysql://, postgres:// or sqlite://path/to/file.db
+    #[arg(long, env = "DATABASE_URL")]
+    pub database_url: Option<String>,
+
+    /// Maximum number of pooled database connections
+    #[arg(long, env = "DATABASE_POOL_SIZE")]
+    pub database_pool_size: Option<usize>,
+
+    /// Days of inactivity after which a login session expires
+    #[arg(long, env = "SESSION_DURATION_DAYS")]
+    pub session_duration_days: Option<i64>,
+
+    /// Minutes between two purges of expired sessions
+    #[arg(long, env = "SESSION_CLEANUP_INTERVAL_MINUTES")]
+    pub session_cleanup_interval_minutes: Option<u64>,
+
+    /// Hours an invitation link stays valid
+    #[arg(long, env = "INVITATION_DURATION_HOURS")]
+    pub invitation_duration_hours: Option<u32>,
+
+    /// Serve static files from this directory instead of the embedded copy (development)
+    #[arg(long, env = "STATIC_DIR")]
+    pub static_dir: Option<PathBuf>,
+
+    /// Apply pending database migrations when the server starts (`--auto-migrate false` to disable)
+    #[arg(long, env = "AUTO_MIGRATE")]
+    pub auto_migrate: Option<bool>,
+
+    /// Log filter, e.g. `debug` or `info,gt6_vein_manager=debug`
+    #[arg(long, env = "LOG_LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Log output format
+    #[arg(long, env = "LOG_FORMAT", value_enum)]
+    pub log_format: Option<LogFormat>,
+
+    /// Also serve `/metrics` without login on this address, e.g. `127.0.0.1:9100`
+    #[arg(long, env = "METRICS_ADDRESS")]
+    pub metrics_address: Option<String>,
+
+    /// Header carrying the client address set by a reverse proxy, e.g. `X-Forwarded-For`.
+    /// Only set it when every request comes through that proxy
+    #[arg(long, env = "TRUSTED_PROXY_HEADER")]
+    pub trusted_proxy_header: Option<String>,
+}
+
+/// Contents of the TOML config file. Every key is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    bind_address: Option<String>,
+    port: Option<u16>,
+    public_url: Option<String>,
+    database_url: Option<String>,
+    database_pool_size: Option<usize>,
+    session_duration_days: Option<i64>,
+    session_cleanup_interval_minutes: Option<u64>,
+    invitation_duration_hours: Option<u32>,
+    static_dir: Option<PathBuf>,
+    auto_migrate: Option<bool>,
+    log_level: Option<String>,
+    log_format: Option<LogFormat>,
+    metrics_address: Option<String>,
+    trusted_proxy_header: Option<String>,
+}
+
+impl FileConfig {
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read config file {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse config file {}: {}", path.display(), e))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub bind_address: String,
+    pub port: u16,
+    pub public_url: String,
+    pub database_url: String,
+    pub database_pool_size: usize,
+    pub session_duration_days: i64,
+    pub session_cleanup_interval_minutes: u64,
+    pub invitation_duration_hours: u32,
+    pub static_dir: Option<PathBuf>,
+    pub auto_migrate: bool,
+    pub log_level: String,
+    pub log_format: LogFormat,
+    pub metrics_address: Option<String>,
+    pub trusted_proxy_header: Option<String>,
+}
+
+impl AppConfig {
+    /// Merges the config file with flags and environment variables (already folded into `args`).
+    pub fn load(args: ConfigArgs) -> anyhow::Result<Self> {
+        let file = match &args.config {
+            Some(path) => FileConfig::read(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                FileConfig::read(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => FileConfig::default(),
+        };
+
+        let port = args.port.or(file.port).unwrap_or_else(|| {
+            eprintln!(
+                "PORT is not configured, using default port {}",
+                DEFAULT_PORT
+            );
+            DEFAULT_PORT
+        });
+
+        let public_url = args
+            .public_url
+            .or(file.public_url)
+            .unwrap_or_else(|| legacy_public_url(port));
+
+        let database_url = args
+            .database_url
+            .or(file.database_url)
+            .ok_or_else(|| anyhow::anyhow!("DATABASE_URL must be set"))?;
+
+        let config = AppConfig {
+            bind_address: args
+                .bind_address
+                .or(file.bind_address)
+                .unwrap_or_else(|| DEFAULT_BIND_ADDRESS.to_string()),
+            port,
+            public_url: public_url.trim_end_matches('/').to_string(),
+            database_url,
+            database_pool_size: args
+                .database_pool_size
+                .or(file.database_pool_size)
+                .unwrap_or(DEFAULT_DATABASE_POOL_SIZE),
+            session_duration_days: args
+                .session_duration_days
+                .or(file.session_duration_days)
+                .unwrap_or(DEFAULT_SESSION_DURATION_DAYS),
+            session_cleanup_interval_minutes: args
+                .session_cleanup_interval_minutes
+                .or(file.session_cleanup_interval_minutes)
+                .unwrap_or(DEFAULT_SESSION_CLEANUP_INTERVAL_MINUTES),
+            invitation_duration_hours: args
+                .invitation_duration_hours
+                .or(file.invitation_duration_hours)
+                .unwrap_or(DEFAULT_INVITATION_DURATION_HOURS),
+            static_dir: args.static_dir.or(file.static_dir),
+            auto_migrate: args.auto_migrate.or(file.auto_migrate).unwrap_or(true),
+            log_level: args
+                .log_level
+                .or(file.log_level)
+                .unwrap_or_else(|| DEFAULT_LOG_LEVEL.to_string()),
+            log_format: args.log_format.or(file.log_format).unwrap_or_default(),
+            metrics_address: args.metrics_address.or(file.metrics_address),
+            trusted_proxy_header: args
+                .trusted_proxy_header
+                .or(file.trusted_proxy_header)
+                .filter(|header| !header.trim().is_empty()),
+        };
+
+        if config.database_pool_size == 0 {
+            anyhow::bail!("database_pool_size must be at least 1");
+        }
+        if config.session_duration_days <= 0 {
+            anyhow::bail!("session_duration_days must be positive");
+        }
+        if config.session_cleanup_interval_minutes == 0 {
+            anyhow::bail!("session_cleanup_interval_minutes must be positive");
+        }
+        if config.invitation_duration_hours == 0 {
+            anyhow::bail!("invitation_duration_hours must be positive");
+        }
+        if let Some(header) = &config.trusted_proxy_header {
+            header
+                .parse::<axum::http::HeaderName>()
+                .map_err(|_| anyhow::anyhow!("trusted_proxy_header is not a header name"))?;
+        }
+
+        Ok(config)
+    }
+
+    pub fn server_address(&self) -> String {
+        format!("{}:{}", self.bind_address, self.port)
+    }
+
+    /// Address of the client of a request received from `peer`.
+    ///
+    /// With `trusted_proxy_header` set, the last address in that header is used: the one the
+    /// proxy appended for the connection it accepted. Falls back to `peer` when the header is
+    /// missing or not an address.
+    pub fn client_ip(&self, headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+        self.trusted_proxy_header
+            .as_deref()
+            .and_then(|name| headers.get_all(name).iter().next_back())
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|address| address.trim().parse().ok())
+            .unwrap_or_else(|| peer.ip())
+    }
+
+    /// Link a new user opens to register with the given invitation token.
+    pub fn invitation_url(&self, token: &str) -> String {
+        format!("{}/auth/register?token={}", self.public_url, token)
+    }
+}
+
+/// Builds the public URL from the older `PROTOCOL` / `HOST` variables when `PUBLIC_URL` is absent.
+///
+/// Shells and containers often set `HOST` for their own use, so it is only read together with
+/// `PROTOCOL`.
+fn legacy_public_url(port: u16) -> String {
+    let (protocol, host) = match env::var("PROTOCOL") {
+        Ok(protocol) => (
+            protocol,
+            env::var("HOST").unwrap_or_else(|_| "localhost".to_string()),
+        ),
+        Err(_) => ("http".to_string(), "localhost".to_string()),
+    };
+    format!("{}://{}:{}", protocol, host, port)
+}
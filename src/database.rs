@@ -1,2 +1,4 @@
 pub mod connection;
+pub mod migrations;
 pub mod queries;
+pub mod repository;
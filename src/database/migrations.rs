@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use diesel::backend::Backend;
+use diesel::migration::MigrationSource;
+use diesel::{Connection, MysqlConnection};
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
+
+use crate::database::connection::DatabaseBackend;
+
+/// `migrations/` 以下のバックエンドごとのディレクトリをバイナリに埋め込む
+pub const MYSQL_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/mysql");
+#[cfg(feature = "sqlite")]
+pub const SQLITE_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/sqlite");
+#[cfg(feature = "postgres")]
+pub const POSTGRES_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/postgres");
+
+/// Applies every pending migration and returns the versions that were run.
+///
+/// Fails without touching the database if it has migrations this binary does not know.
+pub async fn run_pending_migrations(database_url: &str) -> Result<Vec<String>> {
+    migrate(database_url, true).await
+}
+
+/// Checks the schema before the server starts, applying pending migrations when `auto_migrate` is set.
+pub async fn prepare_database(database_url: &str, auto_migrate: bool) -> Result<()> {
+    for version in migrate(database_url, auto_migrate).await? {
+        tracing::info!(version = %version, "Applied migration");
+    }
+    Ok(())
+}
+
+/// How long the readiness probe reuses the result of a migration check.
+pub const MIGRATION_CHECK_INTERVAL_SECONDS: u64 = 60;
+
+/// When the migrations were last compared, and the outcome.
+type MigrationCheck = (Instant, Result<(), String>);
+
+/// Whether the migrations of this binary match the database, as reported by `/readyz`.
+///
+/// A check opens its own connection, so its result is kept for
+/// `MIGRATION_CHECK_INTERVAL_SECONDS` and concurrent probes wait for the running one.
+#[derive(Clone)]
+pub struct MigrationStatus {
+    database_url: Arc<str>,
+    last_check: Arc<tokio::sync::Mutex<Option<MigrationCheck>>>,
+}
+
+impl MigrationStatus {
+    /// Status that is checked on the first call to [`MigrationStatus::check`].
+    pub fn new(database_url: &str) -> Self {
+        Self {
+            database_url: database_url.into(),
+            last_check: Arc::default(),
+        }
+    }
+
+    /// Status of a database that was just checked by [`prepare_database`].
+    pub fn prepared(database_url: &str) -> Self {
+        Self {
+            database_url: database_url.into(),
+            last_check: Arc::new(tokio::sync::Mutex::new(Some((Instant::now(), Ok(()))))),
+        }
+    }
+
+    /// Fails with the reason while migrations are pending or the schema is newer than the binary.
+    /// Nothing is applied.
+    pub async fn check(&self) -> Result<(), String> {
+        let mut last_check = self.last_check.lock().await;
+        let interval = Duration::from_secs(MIGRATION_CHECK_INTERVAL_SECONDS);
+        if let Some((_, result)) = last_check
+            .as_ref()
+            .filter(|(checked_at, _)| checked_at.elapsed() < interval)
+        {
+            return result.clone();
+        }
+
+        let result = migrate(&self.database_url, false)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string());
+        *last_check = Some((Instant::now(), result.clone()));
+        result
+    }
+}
+
+async fn migrate(database_url: &str, apply: bool) -> Result<Vec<String>> {
+    let backend = DatabaseBackend::from_url(database_url)?;
+
+    // diesel_migrations は同期接続を必要とするため、ブロッキングスレッドで実行する
+    tokio::task::spawn_blocking(move || match backend {
+        DatabaseBackend::Mysql { url } => {
+            let mut connection = MysqlConnection::establish(&url)?;
+            migrate_connection(&mut connection, MYSQL_MIGRATIONS, apply)
+        }
+        #[cfg(feature = "sqlite")]
+        DatabaseBackend::Sqlite { path } => {
+            let mut connection = diesel::SqliteConnection::establish(&path)?;
+            migrate_connection(&mut connection, SQLITE_MIGRATIONS, apply)
+        }
+        // libpq に依存しないよう、非同期接続を同期接続として包んで使う
+        #[cfg(feature = "postgres")]
+        DatabaseBackend::Postgres { url } => {
+            let mut connection = diesel_async::async_connection_wrapper::AsyncConnectionWrapper::<
+                diesel_async::AsyncPgConnection,
+            >::establish(&url)?;
+            migrate_connection(&mut connection, POSTGRES_MIGRATIONS, apply)
+        }
+    })
+    .await?
+}
+
+/// Runs pending migrations when `apply` is set, otherwise refuses to continue while any are pending.
+fn migrate_connection<DB: Backend>(
+    connection: &mut impl MigrationHarness<DB>,
+    migrations: EmbeddedMigrations,
+    apply: bool,
+) -> Result<Vec<String>> {
+    ensure_schema_not_ahead(connection, &migrations)?;
+
+    if apply {
+        let applied = connection
+            .run_pending_migrations(migrations)
+            .map_err(|e| anyhow::anyhow!("Failed to run migrations: {}", e))?;
+        return Ok(applied.iter().map(|version| version.to_string()).collect());
+    }
+
+    let pending = connection
+        .pending_migrations(migrations)
+        .map_err(|e| anyhow::anyhow!("Failed to list pending migrations: {}", e))?;
+    if !pending.is_empty() {
+        anyhow::bail!(
+            "The database has {} pending migrations and auto-migration is disabled. \
+             Run the `migrate` subcommand first.",
+            pending.len()
+        );
+    }
+    Ok(Vec::new())
+}
+
+/// Refuses to continue when the database was migrated by a newer version of the binary.
+fn ensure_schema_not_ahead<DB: Backend>(
+    connection: &mut impl MigrationHarness<DB>,
+    migrations: &EmbeddedMigrations,
+) -> Result<()> {
+    let known: HashSet<String> = MigrationSource::<DB>::migrations(migrations)
+        .map_err(|e| anyhow::anyhow!("Failed to read embedded migrations: {}", e))?
+        .iter()
+        .map(|migration| migration.name().version().to_string())
+        .collect();
+
+    let unknown: Vec<String> = connection
+        .applied_migrations()
+        .map_err(|e| anyhow::anyhow!("Failed to read applied migrations: {}", e))?
+        .iter()
+        .map(|version| version.to_string())
+        .filter(|version| !known.contains(version))
+        .collect();
+
+    if !unknown.is_empty() {
+        anyhow::bail!(
+            "The database schema is newer than this binary (unknown migrations: {}). \
+             Upgrade gt6-vein-manager before starting it against this database.",
+            unknown.join(", ")
+        );
+    }
+    Ok(())
+}
@@ -1,17 +1,37 @@
-use crate::models::forms::SearchQuery;
+use crate::database::connection::{DbConnection, with_connection};
+use crate::models::forms::{NoteScope, SearchQuery, SortKey, SortOrder};
+use crate::models::saved_search::SavedSearch;
+use crate::models::vein::Vein;
+use crate::ores::{OreRole, VeinOre, normalize_composition, vein_types_yielding};
 use crate::schema::*;
+use diesel::dsl::{not, sql};
+use diesel::sql_types::{BigInt, Bool, Integer, Nullable, Text};
 use diesel::{
-    ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, SelectableHelper,
-    TextExpressionMethods, insert_into,
+    BoolExpressionMethods, EscapeExpressionMethods, ExpressionMethods, QueryDsl, QueryResult,
+    SelectableHelper, TextExpressionMethods, insert_into,
 };
-use diesel_async::{AsyncMysqlConnection, RunQueryDsl};
+use diesel_async::RunQueryDsl;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
+diesel::define_sql_function! {
+    /// SQL `LOWER`. PostgreSQL compares `LIKE` case-sensitively, so both sides are lowered.
+    fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text;
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct VeinWithStatus {
     pub id: String,
     pub name: String,
     pub x_coord: i32,
-    pub y_coord: Option<i32>,
+    /// Lowest and highest Y of the vein. Exports made before ranges were recorded have a
+    /// single `y_coord`, read as the lowest Y.
+    #[serde(default, alias = "y_coord")]
+    pub y_min: Option<i32>,
+    #[serde(default)]
+    pub y_max: Option<i32>,
     pub z_coord: i32,
     pub notes: Option<String>,
     pub created_at: Option<chrono::NaiveDateTime>,
@@ -19,12 +39,63 @@ pub struct VeinWithStatus {
     pub depleted: bool,
     pub revoked: bool,
     pub is_bedrock: bool,
+    /// Ore materials, by role. Missing in exports made before compositions were recorded.
+    #[serde(default)]
+    pub ores: Vec<VeinOre>,
 }
 
 impl VeinWithStatus {
-    pub fn format_y_coord(&self) -> String {
-        self.y_coord
-            .map_or_else(|| "-".to_string(), |y| y.to_string())
+    /// Whether the vein yields the material: from its composition, or from its vein type when
+    /// no composition is recorded. Matches the material filter of the SQL search.
+    pub fn yields(&self, material: &str) -> bool {
+        if self.ores.is_empty() {
+            return vein_types_yielding(material)
+                .iter()
+                .any(|vein_type| vein_type.to_lowercase() == self.name.to_lowercase());
+        }
+        self.ores
+            .iter()
+            .any(|ore| ore.material.to_lowercase() == material.to_lowercase())
+    }
+
+    /// Materials of the vein with their roles, e.g. `Tin (主鉱石), Cassiterite (中間層)`.
+    pub fn format_ores(&self) -> String {
+        if self.ores.is_empty() {
+            return "-".to_string();
+        }
+        self.ores
+            .iter()
+            .map(|ore| format!("{} ({})", ore.material, ore.role.label()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The Y range of the vein. A single known end is a vein one block high.
+    pub fn y_range(&self) -> Option<(i32, i32)> {
+        match (self.y_min, self.y_max) {
+            (Some(min), Some(max)) => Some((min.min(max), min.max(max))),
+            (Some(y), None) | (None, Some(y)) => Some((y, y)),
+            (None, None) => None,
+        }
+    }
+
+    /// Whether some part of the vein lies between the two heights, both included.
+    /// Veins of unknown height never match, like the SQL search.
+    pub fn reachable_within(&self, y_from: Option<i32>, y_to: Option<i32>) -> bool {
+        if y_from.is_none() && y_to.is_none() {
+            return true;
+        }
+        self.y_range().is_some_and(|(min, max)| {
+            y_from.is_none_or(|from| max >= from) && y_to.is_none_or(|to| min <= to)
+        })
+    }
+
+    pub fn format_y_range(&self) -> String {
+        match self.y_range() {
+            Some((min, max)) if min == max => min.to_string(),
+            Some((min, max)) => format!("{}〜{}", min, max),
+            None => "-".to_string(),
+        }
     }
 
     pub fn format_notes(&self) -> &str {
@@ -57,221 +128,629 @@ impl VeinWithStatus {
             "いいえ"
         }
     }
+
+    /// Squared horizontal distance from `(x, z)`, as compared by the distance sort.
+    pub fn distance_squared_from(&self, (x, z): (i32, i32)) -> i64 {
+        let dx = i64::from(self.x_coord) - i64::from(x);
+        let dz = i64::from(self.z_coord) - i64::from(z);
+        dx * dx + dz * dz
+    }
+
+    pub fn format_distance_from(&self, origin: (i32, i32)) -> String {
+        format!("{:.0}", (self.distance_squared_from(origin) as f64).sqrt())
+    }
+
+    /// Position in the status sort: confirmed, unconfirmed, depleted, then revoked veins.
+    pub fn status_rank(&self) -> i32 {
+        if self.revoked {
+            3
+        } else if self.depleted {
+            2
+        } else if self.confirmed {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+/// Time recorded with a new vein, status change or note, in UTC like every backend's column default.
+///
+/// The column default only has one-second resolution, so changes made within the same second
+/// could not be told apart when picking the latest one. MySQL connections run with a UTC session
+/// time zone (see `create_diesel_pool`), so rows written by the default stay comparable.
+fn history_timestamp() -> chrono::NaiveDateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+/// Escape character of the patterns built by [`contains_pattern`].
+const LIKE_ESCAPE: char = '\\';
+
+/// `LIKE` pattern matching `term` anywhere, with `%`, `_` and the escape character taken literally.
+///
+/// Must be used with `ESCAPE` [`LIKE_ESCAPE`]; the character is bound rather than written into the
+/// SQL, since backends disagree on backslashes in string literals.
+fn contains_pattern(term: &str) -> String {
+    let mut pattern = String::with_capacity(term.len() + 2);
+    pattern.push('%');
+    for c in term.chars() {
+        if matches!(c, '%' | '_' | LIKE_ESCAPE) {
+            pattern.push(LIKE_ESCAPE);
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// SQL for the latest value of a status of the vein in the outer query, `FALSE` if never recorded.
+fn latest_status_sql(table: &str, column: &str) -> String {
+    format!(
+        "COALESCE((SELECT {table}.{column} FROM {table} WHERE {table}.vein_id = vein.id \
+         ORDER BY {table}.created_at DESC LIMIT 1), FALSE)"
+    )
+}
+
+fn latest_note_sql() -> &'static str {
+    "(SELECT vein_note.note FROM vein_note WHERE vein_note.vein_id = vein.id \
+     ORDER BY vein_note.created_at DESC LIMIT 1)"
+}
+
+/// Rank used by the status sort: confirmed, unconfirmed, depleted, then revoked veins.
+fn status_rank_sql() -> String {
+    format!(
+        "CASE WHEN {} THEN 3 WHEN {} THEN 2 WHEN {} THEN 0 ELSE 1 END",
+        latest_status_sql("vein_revocation", "revoked"),
+        latest_status_sql("vein_depletion", "depleted"),
+        latest_status_sql("vein_confirmation", "confirmed"),
+    )
+}
+
+// 検索条件の絞り込みを一覧の取得と件数の取得で共有する
+macro_rules! filter_veins {
+    ($query:expr, $search_query:expr) => {{
+        let mut query = $query;
+        if let Some(name_filter) = $search_query.get_name_filter() {
+            query = query.filter(
+                lower(vein::name)
+                    .like(contains_pattern(&name_filter.to_lowercase()))
+                    .escape(LIKE_ESCAPE),
+            );
+        }
+        // 取り消された鉱脈は明示されない限り除外する
+        if !$search_query.should_include_revoked() {
+            query = query.filter(sql::<Bool>(&format!(
+                "NOT {}",
+                latest_status_sql("vein_revocation", "revoked")
+            )));
+        }
+        for (wanted, table, column) in [
+            ($search_query.confirmed, "vein_confirmation", "confirmed"),
+            ($search_query.depleted, "vein_depletion", "depleted"),
+            ($search_query.bedrock, "vein_is_bedrock", "is_bedrock"),
+        ] {
+            if let Some(wanted) = wanted {
+                let status = latest_status_sql(table, column);
+                query = query.filter(sql::<Bool>(&if wanted {
+                    status
+                } else {
+                    format!("NOT {}", status)
+                }));
+            }
+        }
+        // キーワードはすべて、名前かメモのどちらかに含まれる必要がある
+        for term in $search_query.text_terms() {
+            let pattern = contains_pattern(&term);
+            let (note_sql, close) = match $search_query.note_scope() {
+                NoteScope::Latest => (
+                    format!("LOWER(COALESCE({}, '')) LIKE ", latest_note_sql()),
+                    "",
+                ),
+                NoteScope::All => (
+                    "EXISTS (SELECT 1 FROM vein_note WHERE vein_note.vein_id = vein.id \
+                     AND LOWER(vein_note.note) LIKE "
+                        .to_string(),
+                    ")",
+                ),
+            };
+            query = query.filter(
+                lower(vein::name)
+                    .like(pattern.clone())
+                    .escape(LIKE_ESCAPE)
+                    .or(sql::<Bool>(&note_sql)
+                        .bind::<Text, _>(pattern)
+                        .sql(" ESCAPE ")
+                        .bind::<Text, _>(LIKE_ESCAPE.to_string())
+                        .sql(close)),
+            );
+        }
+        // 層を問わず、指定した鉱石を含む鉱脈
+        // 構成が記録されていない鉱脈は、名前が一致する鉱脈の種類の構成で判断する
+        if let Some(material) = $search_query.get_material_filter() {
+            let vein_types: Vec<String> = vein_types_yielding(material)
+                .into_iter()
+                .map(str::to_lowercase)
+                .collect();
+            query = query.filter(
+                vein::id
+                    .eq_any(
+                        vein_ore::table
+                            .filter(lower(vein_ore::material).eq(material.to_lowercase()))
+                            .select(vein_ore::vein_id),
+                    )
+                    .or(not(vein::id.eq_any(vein_ore::table.select(vein_ore::vein_id)))
+                        .and(lower(vein::name).eq_any(vein_types))),
+            );
+        }
+        if let Some(user_id) = &$search_query.favorites_of {
+            query = query.filter(
+                vein::id.eq_any(
+                    favorite_vein::table
+                        .filter(favorite_vein::user_id.eq(user_id.clone()))
+                        .select(favorite_vein::vein_id),
+                ),
+            );
+        }
+        if let Some(since) = $search_query.created_since() {
+            query = query.filter(vein::created_at.ge(since));
+        }
+        if let Some(until) = $search_query.created_until() {
+            query = query.filter(vein::created_at.lt(until));
+        }
+        // 指定した高さの範囲と一部でも重なる鉱脈
+        if let Some(y_from) = $search_query.y_from {
+            query = query.filter(vein::y_max.ge(y_from));
+        }
+        if let Some(y_to) = $search_query.y_to {
+            query = query.filter(vein::y_min.le(y_to));
+        }
+        query
+    }};
 }
 
+/// Every vein matching the query, in the requested order, ignoring `page` and `limit`.
 pub async fn search_veins(
-    connection: &mut AsyncMysqlConnection,
+    connection: &mut DbConnection,
     search_query: &SearchQuery,
 ) -> QueryResult<Vec<VeinWithStatus>> {
-    use crate::schema::vein::dsl::*;
-    use crate::schema::vein_confirmation::dsl as vc_dsl;
-    use crate::schema::vein_depletion::dsl as vd_dsl;
-    use crate::schema::vein_is_bedrock::dsl as vb_dsl;
-    use crate::schema::vein_note::dsl as vn_dsl;
-    use crate::schema::vein_revocation::dsl as vr_dsl;
-
-    let mut query = vein.into_boxed();
-
-    // Apply name filter if provided
-    if let Some(name_filter) = search_query.get_name_filter() {
-        query = query.filter(name.like(format!("%{}%", name_filter)));
-    }
+    load_veins(connection, search_query, None).await
+}
 
-    // Execute the main query to get veins
-    let veins: Vec<crate::models::vein::Vein> = query
-        .select(crate::models::vein::Vein::as_select())
-        .load(connection)
-        .await?;
-
-    let mut results = Vec::new();
-
-    for vein_record in veins {
-        // Get latest confirmation status
-        let latest_confirmed = vein_confirmation::table
-            .filter(vc_dsl::vein_id.eq(&vein_record.id))
-            .order(vc_dsl::created_at.desc())
-            .select(vc_dsl::confirmed)
-            .first::<Option<bool>>(connection)
-            .await
-            .optional()?
-            .flatten()
-            .unwrap_or(false);
-
-        // Get latest depletion status
-        let latest_depleted = vein_depletion::table
-            .filter(vd_dsl::vein_id.eq(&vein_record.id))
-            .order(vd_dsl::created_at.desc())
-            .select(vd_dsl::depleted)
-            .first::<Option<bool>>(connection)
-            .await
-            .optional()?
-            .flatten()
-            .unwrap_or(false);
-
-        // Get latest revocation status
-        let latest_revoked = vein_revocation::table
-            .filter(vr_dsl::vein_id.eq(&vein_record.id))
-            .order(vr_dsl::created_at.desc())
-            .select(vr_dsl::revoked)
-            .first::<Option<bool>>(connection)
-            .await
-            .optional()?
-            .flatten()
-            .unwrap_or(false);
-
-        // Get latest bedrock status
-        let latest_is_bedrock = vein_is_bedrock::table
-            .filter(vb_dsl::vein_id.eq(&vein_record.id))
-            .order(vb_dsl::created_at.desc())
-            .select(vb_dsl::is_bedrock)
-            .first::<Option<bool>>(connection)
+/// The requested page of veins matching the query, with the number of matches on all pages.
+pub async fn search_veins_page(
+    connection: &mut DbConnection,
+    search_query: &SearchQuery,
+) -> QueryResult<(Vec<VeinWithStatus>, i64)> {
+    let page = (
+        i64::from(search_query.limit()),
+        i64::try_from(search_query.offset()).unwrap_or(i64::MAX),
+    );
+    let veins = load_veins(connection, search_query, Some(page)).await?;
+    let total = with_connection!(connection, |connection| {
+        filter_veins!(vein::table.into_boxed(), search_query)
+            .count()
+            .get_result::<i64>(connection)
             .await
-            .optional()?
-            .flatten()
-            .unwrap_or(false);
-
-        // Get latest note
-        let latest_notes = vein_note::table
-            .filter(vn_dsl::vein_id.eq(&vein_record.id))
-            .order(vn_dsl::created_at.desc())
-            .select(vn_dsl::note)
-            .first::<Option<String>>(connection)
+    })?;
+    Ok((veins, total))
+}
+
+async fn load_veins(
+    connection: &mut DbConnection,
+    search_query: &SearchQuery,
+    page: Option<(i64, i64)>,
+) -> QueryResult<Vec<VeinWithStatus>> {
+    // 最新の状態とメモは相関サブクエリで鉱脈と一緒に取得する
+    let confirmed_sql = latest_status_sql("vein_confirmation", "confirmed");
+    let depleted_sql = latest_status_sql("vein_depletion", "depleted");
+    let revoked_sql = latest_status_sql("vein_revocation", "revoked");
+    let is_bedrock_sql = latest_status_sql("vein_is_bedrock", "is_bedrock");
+    let status_rank_sql = status_rank_sql();
+    let (from_x, from_z) = search_query.origin();
+    let descending = search_query.sort_order() == SortOrder::Desc;
+
+    // ボックス化したクエリはバックエンドごとの型になるため、接続の分岐の中で組み立てる
+    let rows: Vec<(Vein, bool, bool, bool, bool, Option<String>)> =
+        with_connection!(connection, |connection| {
+            let query = vein::table
+                .select((
+                    Vein::as_select(),
+                    sql::<Bool>(&confirmed_sql),
+                    sql::<Bool>(&depleted_sql),
+                    sql::<Bool>(&revoked_sql),
+                    sql::<Bool>(&is_bedrock_sql),
+                    sql::<Nullable<Text>>(latest_note_sql()),
+                ))
+                .into_boxed();
+            let mut query = filter_veins!(query, search_query);
+
+            query = match (search_query.sort_key(), descending) {
+                (SortKey::Name, false) => query.order(lower(vein::name).asc()),
+                (SortKey::Name, true) => query.order(lower(vein::name).desc()),
+                (SortKey::CreatedAt, false) => query.order(vein::created_at.asc()),
+                (SortKey::CreatedAt, true) => query.order(vein::created_at.desc()),
+                (SortKey::Status, false) => query.order(sql::<Integer>(&status_rank_sql).asc()),
+                (SortKey::Status, true) => query.order(sql::<Integer>(&status_rank_sql).desc()),
+                (SortKey::Distance, _) => {
+                    // 水平距離の2乗で並べる（座標の差の2乗が32ビットを超えないよう BIGINT で計算する）
+                    let (from_x, from_z) = (i64::from(from_x), i64::from(from_z));
+                    let distance = sql::<BigInt>("(vein.x_coord - ")
+                        .bind::<BigInt, _>(from_x)
+                        .sql(") * (vein.x_coord - ")
+                        .bind::<BigInt, _>(from_x)
+                        .sql(") + (vein.z_coord - ")
+                        .bind::<BigInt, _>(from_z)
+                        .sql(") * (vein.z_coord - ")
+                        .bind::<BigInt, _>(from_z)
+                        .sql(")");
+                    if descending {
+                        query.order(distance.desc())
+                    } else {
+                        query.order(distance.asc())
+                    }
+                }
+            };
+            // 同じ値の鉱脈がページをまたいで入れ替わらないよう、ID で順序を確定させる
+            query = query.then_order_by(vein::id.asc());
+
+            if let Some((limit, offset)) = page {
+                query = query.limit(limit).offset(offset);
+            }
+            query.load(connection).await
+        })?;
+
+    let mut veins: Vec<VeinWithStatus> = rows
+        .into_iter()
+        .map(
+            |(vein_record, confirmed, depleted, revoked, is_bedrock, notes)| VeinWithStatus {
+                id: vein_record.id,
+                name: vein_record.name,
+                x_coord: vein_record.x_coord,
+                y_min: vein_record.y_min,
+                y_max: vein_record.y_max,
+                z_coord: vein_record.z_coord,
+                notes,
+                created_at: vein_record.created_at,
+                confirmed,
+                depleted,
+                revoked,
+                is_bedrock,
+                ores: Vec::new(),
+            },
+        )
+        .collect();
+
+    attach_ores(connection, &mut veins).await?;
+    Ok(veins)
+}
+
+/// Number of vein ids sent in one `IN` list when loading compositions.
+const ORE_LOOKUP_CHUNK: usize = 500;
+
+/// Loads the compositions of the veins in one query per chunk of veins.
+async fn attach_ores(
+    connection: &mut DbConnection,
+    veins: &mut [VeinWithStatus],
+) -> QueryResult<()> {
+    let mut ores_by_vein: HashMap<String, Vec<VeinOre>> = HashMap::new();
+    for chunk in veins.chunks(ORE_LOOKUP_CHUNK) {
+        let ids: Vec<&str> = chunk.iter().map(|vein| vein.id.as_str()).collect();
+        let rows: Vec<(String, String, String)> = with_connection!(connection, |connection| {
+            vein_ore::table
+                .filter(vein_ore::vein_id.eq_any(&ids))
+                .select((vein_ore::vein_id, vein_ore::material, vein_ore::role))
+                .load(connection)
+                .await
+        })?;
+        for (vein_id, material, role) in rows {
+            match role.parse::<OreRole>() {
+                Ok(role) => ores_by_vein
+                    .entry(vein_id)
+                    .or_default()
+                    .push(VeinOre::new(material, role)),
+                Err(e) => tracing::warn!(vein_id = %vein_id, error = %e, "Skipping vein ore"),
+            }
+        }
+    }
+
+    for vein in veins {
+        if let Some(mut ores) = ores_by_vein.remove(&vein.id) {
+            normalize_composition(&mut ores);
+            vein.ores = ores;
+        }
+    }
+    Ok(())
+}
+
+/// Number of veins that are not revoked and yield one material, as counted by the database.
+#[derive(diesel::QueryableByName)]
+pub struct MaterialVeinCount {
+    /// Lowercased material name
+    #[diesel(sql_type = Text)]
+    pub material: String,
+    #[diesel(sql_type = BigInt)]
+    pub total: i64,
+    #[diesel(sql_type = BigInt)]
+    pub confirmed: i64,
+    #[diesel(sql_type = BigInt)]
+    pub depleted: i64,
+}
+
+/// Vein counts of the materials yielding the most veins that are not revoked, at most `limit`.
+///
+/// Veins without a recorded composition are not counted.
+pub async fn count_veins_by_material(
+    connection: &mut DbConnection,
+    limit: i64,
+) -> QueryResult<Vec<MaterialVeinCount>> {
+    // 同じ鉱石が複数の役割で記録されていても、鉱脈は1回だけ数える
+    let query = format!(
+        "SELECT LOWER(vein_ore.material) AS material, \
+         COUNT(DISTINCT vein.id) AS total, \
+         COUNT(DISTINCT CASE WHEN {confirmed} THEN vein.id END) AS confirmed, \
+         COUNT(DISTINCT CASE WHEN {depleted} THEN vein.id END) AS depleted \
+         FROM vein INNER JOIN vein_ore ON vein_ore.vein_id = vein.id \
+         WHERE NOT {revoked} \
+         GROUP BY LOWER(vein_ore.material) \
+         ORDER BY total DESC, material ASC \
+         LIMIT {limit}",
+        confirmed = latest_status_sql("vein_confirmation", "confirmed"),
+        depleted = latest_status_sql("vein_depletion", "depleted"),
+        revoked = latest_status_sql("vein_revocation", "revoked"),
+    );
+    with_connection!(connection, |connection| {
+        diesel::sql_query(&query).load(connection).await
+    })
+}
+
+/// Materials recorded in any composition, without repeats.
+pub async fn list_ore_materials(connection: &mut DbConnection) -> QueryResult<Vec<String>> {
+    with_connection!(connection, |connection| {
+        vein_ore::table
+            .select(vein_ore::material)
+            .distinct()
+            .order(vein_ore::material.asc())
+            .load(connection)
             .await
-            .optional()?
-            .flatten();
-
-        // Skip revoked veins unless explicitly requested
-        if latest_revoked && !search_query.should_include_revoked() {
-            continue;
-        }
-
-        let vein_with_status = VeinWithStatus {
-            id: vein_record.id,
-            name: vein_record.name,
-            x_coord: vein_record.x_coord,
-            y_coord: vein_record.y_coord,
-            z_coord: vein_record.z_coord,
-            notes: latest_notes,
-            created_at: vein_record.created_at,
-            confirmed: latest_confirmed,
-            depleted: latest_depleted,
-            revoked: latest_revoked,
-            is_bedrock: latest_is_bedrock,
-        };
+    })
+}
 
-        results.push(vein_with_status);
+/// Replaces the composition of a vein.
+///
+/// Runs in a transaction so that a failed insert never leaves a partial composition behind.
+pub async fn replace_vein_ores(
+    connection: &mut DbConnection,
+    vein_id: &str,
+    ores: &[VeinOre],
+) -> QueryResult<()> {
+    use diesel_async::AsyncConnection;
+
+    tracing::debug!(vein_id = %vein_id, ores = ores.len(), "Replacing vein ores");
+    let vein_id = vein_id.to_string();
+    let rows: Vec<_> = ores
+        .iter()
+        .map(|ore| {
+            (
+                vein_ore::id.eq(Uuid::new_v4().to_string()),
+                vein_ore::vein_id.eq(vein_id.clone()),
+                vein_ore::material.eq(ore.material.clone()),
+                vein_ore::role.eq(ore.role.as_str().to_string()),
+            )
+        })
+        .collect();
+
+    macro_rules! replace_in_transaction {
+        ($connection:expr) => {
+            $connection
+                .transaction::<_, diesel::result::Error, _>(|connection| {
+                    async move {
+                        diesel::delete(vein_ore::table.filter(vein_ore::vein_id.eq(&vein_id)))
+                            .execute(connection)
+                            .await?;
+                        if !rows.is_empty() {
+                            insert_into(vein_ore::table)
+                                .values(rows)
+                                .execute(connection)
+                                .await?;
+                        }
+                        Ok(())
+                    }
+                    .scope_boxed()
+                })
+                .await
+        };
     }
 
-    Ok(results)
+    match connection {
+        DbConnection::Mysql(pooled) => replace_in_transaction!(&mut **pooled),
+        #[cfg(feature = "postgres")]
+        DbConnection::Postgres(pooled) => replace_in_transaction!(&mut **pooled),
+        // diesel-async は SQLite への複数行の INSERT を組み立てられないため、同期接続で実行する
+        #[cfg(feature = "sqlite")]
+        DbConnection::Sqlite(pooled) => {
+            pooled
+                .spawn_blocking(move |connection| {
+                    use diesel::{Connection, RunQueryDsl};
+
+                    connection.transaction(|connection| {
+                        let delete =
+                            diesel::delete(vein_ore::table.filter(vein_ore::vein_id.eq(&vein_id)));
+                        RunQueryDsl::execute(delete, connection)?;
+                        if !rows.is_empty() {
+                            let insert = insert_into(vein_ore::table).values(rows);
+                            RunQueryDsl::execute(insert, connection)?;
+                        }
+                        Ok(())
+                    })
+                })
+                .await
+        }
+    }
 }
 
 pub async fn insert_vein(
-    connection: &mut AsyncMysqlConnection,
+    connection: &mut DbConnection,
     id: &str,
     name: &str,
     x_coord: i32,
-    y_coord: Option<i32>,
+    y_range: Option<(i32, i32)>,
     z_coord: i32,
     notes: &Option<String>,
 ) -> QueryResult<usize> {
-    println!(
-        "Attempting to insert vein: id={}, name={}, x_coord={}, y_coord={:?}, z_coord={}",
-        id, name, x_coord, y_coord, z_coord
+    tracing::debug!(
+        vein_id = %id,
+        name = %name,
+        x_coord,
+        y_range = ?y_range,
+        z_coord,
+        "Inserting vein"
     );
-    let result = insert_into(vein::table)
-        .values((
-            vein::id.eq(id),
-            vein::name.eq(name),
-            vein::x_coord.eq(x_coord),
-            vein::y_coord.eq(y_coord),
-            vein::z_coord.eq(z_coord),
-        ))
-        .execute(connection)
-        .await;
+    let result = with_connection!(connection, |connection| {
+        insert_into(vein::table)
+            .values((
+                vein::id.eq(id),
+                vein::name.eq(name),
+                vein::x_coord.eq(x_coord),
+                vein::y_min.eq(y_range.map(|(min, _)| min)),
+                vein::y_max.eq(y_range.map(|(_, max)| max)),
+                vein::z_coord.eq(z_coord),
+                vein::created_at.eq(history_timestamp()),
+            ))
+            .execute(connection)
+            .await
+    });
 
     match result {
         Ok(count) => {
-            if let Some(note) = notes {
-                if !note.is_empty() {
-                    insert_vein_note(connection, id, note).await?;
-                }
+            if let Some(note) = notes
+                && !note.is_empty()
+            {
+                insert_vein_note(connection, id, note).await?;
             }
-            println!("Successfully inserted vein: id={}, count={}", id, count);
+            tracing::debug!(vein_id = %id, count, "Inserted vein");
             Ok(count)
         }
         Err(e) => {
-            eprintln!("Failed to insert vein: id={}, error={}", id, e);
+            tracing::error!(vein_id = %id, error = %e, "Failed to insert vein");
             Err(e)
         }
     }
 }
 
+/// Restores an exported vein with its current statuses, keeping its id and creation time.
+///
+/// Everything is written in one transaction, so a vein that fails part way leaves no rows behind.
+pub async fn import_vein(
+    connection: &mut DbConnection,
+    record: &VeinWithStatus,
+) -> QueryResult<()> {
+    connection
+        .transaction(|connection| {
+            async move {
+                insert_vein(
+                    connection,
+                    &record.id,
+                    &record.name,
+                    record.x_coord,
+                    record.y_range(),
+                    record.z_coord,
+                    &record.notes,
+                )
+                .await?;
+
+                if let Some(created_at) = record.created_at {
+                    with_connection!(connection, |connection| {
+                        diesel::update(vein::table.filter(vein::id.eq(&record.id)))
+                            .set(vein::created_at.eq(created_at))
+                            .execute(connection)
+                            .await
+                    })?;
+                }
+
+                // 既定値と異なる状態のみ記録する
+                if record.confirmed {
+                    insert_vein_confirmation(connection, &record.id, true).await?;
+                }
+                if record.depleted {
+                    insert_vein_depletion(connection, &record.id, true).await?;
+                }
+                if record.revoked {
+                    insert_vein_revocation(connection, &record.id, true).await?;
+                }
+                if record.is_bedrock {
+                    insert_vein_is_bedrock(connection, &record.id, true).await?;
+                }
+                if !record.ores.is_empty() {
+                    replace_vein_ores(connection, &record.id, &record.ores).await?;
+                }
+
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+}
+
 pub async fn insert_vein_note(
-    connection: &mut AsyncMysqlConnection,
+    connection: &mut DbConnection,
     vein_id: &str,
     note: &str,
 ) -> QueryResult<usize> {
-    println!(
-        "Attempting to insert vein note: vein_id={}, note={}",
-        vein_id, note
-    );
-    let result = insert_into(vein_note::table)
-        .values((
-            vein_note::id.eq(Uuid::new_v4().to_string()),
-            vein_note::vein_id.eq(vein_id),
-            vein_note::note.eq(note),
-        ))
-        .execute(connection)
-        .await;
+    // メモの内容はログに残さない
+    tracing::debug!(vein_id = %vein_id, note_length = note.len(), "Inserting vein note");
+    let result = with_connection!(connection, |connection| {
+        insert_into(vein_note::table)
+            .values((
+                vein_note::id.eq(Uuid::new_v4().to_string()),
+                vein_note::vein_id.eq(vein_id),
+                vein_note::note.eq(note),
+                vein_note::created_at.eq(history_timestamp()),
+            ))
+            .execute(connection)
+            .await
+    });
 
     match result {
         Ok(count) => {
-            println!(
-                "Successfully inserted vein note: vein_id={}, note={}, count={}",
-                vein_id, note, count
-            );
+            tracing::debug!(vein_id = %vein_id, count, "Inserted vein note");
             Ok(count)
         }
         Err(e) => {
-            eprintln!(
-                "Failed to insert vein note: vein_id={}, note={}, error={}",
-                vein_id, note, e
-            );
+            tracing::error!(vein_id = %vein_id, error = %e, "Failed to insert vein note");
             Err(e)
         }
     }
 }
 
 pub async fn insert_vein_confirmation(
-    connection: &mut AsyncMysqlConnection,
+    connection: &mut DbConnection,
     vein_id: &str,
     confirmed: bool,
 ) -> QueryResult<usize> {
-    println!(
-        "Attempting to insert vein confirmation: vein_id={}, confirmed={}",
-        vein_id, confirmed
-    );
-    let result = insert_into(vein_confirmation::table)
-        .values((
-            vein_confirmation::id.eq(Uuid::new_v4().to_string()),
-            vein_confirmation::vein_id.eq(vein_id),
-            vein_confirmation::confirmed.eq(confirmed),
-        ))
-        .execute(connection)
-        .await;
+    tracing::debug!(vein_id = %vein_id, confirmed, "Inserting vein confirmation");
+    let result = with_connection!(connection, |connection| {
+        insert_into(vein_confirmation::table)
+            .values((
+                vein_confirmation::id.eq(Uuid::new_v4().to_string()),
+                vein_confirmation::vein_id.eq(vein_id),
+                vein_confirmation::confirmed.eq(confirmed),
+                vein_confirmation::created_at.eq(history_timestamp()),
+            ))
+            .execute(connection)
+            .await
+    });
 
     match result {
         Ok(count) => {
-            println!(
-                "Successfully inserted vein confirmation: vein_id={}, confirmed={}, count={}",
-                vein_id, confirmed, count
-            );
+            tracing::debug!(vein_id = %vein_id, confirmed, count, "Inserted vein confirmation");
             Ok(count)
         }
         Err(e) => {
-            eprintln!(
-                "Failed to insert vein confirmation: vein_id={}, confirmed={}, error={}",
-                vein_id, confirmed, e
+            tracing::error!(
+                vein_id = %vein_id,
+                confirmed,
+                error = %e,
+                "Failed to insert vein confirmation"
             );
             Err(e)
         }
@@ -279,35 +758,34 @@ pub async fn insert_vein_confirmation(
 }
 
 pub async fn insert_vein_depletion(
-    connection: &mut AsyncMysqlConnection,
+    connection: &mut DbConnection,
     vein_id: &str,
     depleted: bool,
 ) -> QueryResult<usize> {
-    println!(
-        "Attempting to insert vein depletion: vein_id={}, depleted={}",
-        vein_id, depleted
-    );
-    let result = insert_into(vein_depletion::table)
-        .values((
-            vein_depletion::id.eq(Uuid::new_v4().to_string()),
-            vein_depletion::vein_id.eq(vein_id),
-            vein_depletion::depleted.eq(depleted),
-        ))
-        .execute(connection)
-        .await;
+    tracing::debug!(vein_id = %vein_id, depleted, "Inserting vein depletion");
+    let result = with_connection!(connection, |connection| {
+        insert_into(vein_depletion::table)
+            .values((
+                vein_depletion::id.eq(Uuid::new_v4().to_string()),
+                vein_depletion::vein_id.eq(vein_id),
+                vein_depletion::depleted.eq(depleted),
+                vein_depletion::created_at.eq(history_timestamp()),
+            ))
+            .execute(connection)
+            .await
+    });
 
     match result {
         Ok(count) => {
-            println!(
-                "Successfully inserted vein depletion: vein_id={}, depleted={}, count={}",
-                vein_id, depleted, count
-            );
+            tracing::debug!(vein_id = %vein_id, depleted, count, "Inserted vein depletion");
             Ok(count)
         }
         Err(e) => {
-            eprintln!(
-                "Failed to insert vein depletion: vein_id={}, depleted={}, error={}",
-                vein_id, depleted, e
+            tracing::error!(
+                vein_id = %vein_id,
+                depleted,
+                error = %e,
+                "Failed to insert vein depletion"
             );
             Err(e)
         }
@@ -315,35 +793,34 @@ pub async fn insert_vein_depletion(
 }
 
 pub async fn insert_vein_revocation(
-    connection: &mut AsyncMysqlConnection,
+    connection: &mut DbConnection,
     vein_id: &str,
     revoked: bool,
 ) -> QueryResult<usize> {
-    println!(
-        "Attempting to insert vein revocation: vein_id={}, revoked={}",
-        vein_id, revoked
-    );
-    let result = insert_into(vein_revocation::table)
-        .values((
-            vein_revocation::id.eq(Uuid::new_v4().to_string()),
-            vein_revocation::vein_id.eq(vein_id),
-            vein_revocation::revoked.eq(revoked),
-        ))
-        .execute(connection)
-        .await;
+    tracing::debug!(vein_id = %vein_id, revoked, "Inserting vein revocation");
+    let result = with_connection!(connection, |connection| {
+        insert_into(vein_revocation::table)
+            .values((
+                vein_revocation::id.eq(Uuid::new_v4().to_string()),
+                vein_revocation::vein_id.eq(vein_id),
+                vein_revocation::revoked.eq(revoked),
+                vein_revocation::created_at.eq(history_timestamp()),
+            ))
+            .execute(connection)
+            .await
+    });
 
     match result {
         Ok(count) => {
-            println!(
-                "Successfully inserted vein revocation: vein_id={}, revoked={}, count={}",
-                vein_id, revoked, count
-            );
+            tracing::debug!(vein_id = %vein_id, revoked, count, "Inserted vein revocation");
             Ok(count)
         }
         Err(e) => {
-            eprintln!(
-                "Failed to insert vein revocation: vein_id={}, revoked={}, error={}",
-                vein_id, revoked, e
+            tracing::error!(
+                vein_id = %vein_id,
+                revoked,
+                error = %e,
+                "Failed to insert vein revocation"
             );
             Err(e)
         }
@@ -351,37 +828,144 @@ pub async fn insert_vein_revocation(
 }
 
 pub async fn insert_vein_is_bedrock(
-    connection: &mut AsyncMysqlConnection,
+    connection: &mut DbConnection,
     vein_id: &str,
     is_bedrock: bool,
 ) -> QueryResult<usize> {
-    println!(
-        "Attempting to insert vein is_bedrock: vein_id={}, is_bedrock={}",
-        vein_id, is_bedrock
-    );
-    let result = insert_into(vein_is_bedrock::table)
-        .values((
-            vein_is_bedrock::id.eq(Uuid::new_v4().to_string()),
-            vein_is_bedrock::vein_id.eq(vein_id),
-            vein_is_bedrock::is_bedrock.eq(is_bedrock),
-        ))
-        .execute(connection)
-        .await;
+    tracing::debug!(vein_id = %vein_id, is_bedrock, "Inserting vein is_bedrock");
+    let result = with_connection!(connection, |connection| {
+        insert_into(vein_is_bedrock::table)
+            .values((
+                vein_is_bedrock::id.eq(Uuid::new_v4().to_string()),
+                vein_is_bedrock::vein_id.eq(vein_id),
+                vein_is_bedrock::is_bedrock.eq(is_bedrock),
+                vein_is_bedrock::created_at.eq(history_timestamp()),
+            ))
+            .execute(connection)
+            .await
+    });
 
     match result {
         Ok(count) => {
-            println!(
-                "Successfully inserted vein is_bedrock: vein_id={}, is_bedrock={}, count={}",
-                vein_id, is_bedrock, count
-            );
+            tracing::debug!(vein_id = %vein_id, is_bedrock, count, "Inserted vein is_bedrock");
             Ok(count)
         }
         Err(e) => {
-            eprintln!(
-                "Failed to insert vein is_bedrock: vein_id={}, is_bedrock={}, error={}",
-                vein_id, is_bedrock, e
+            tracing::error!(
+                vein_id = %vein_id,
+                is_bedrock,
+                error = %e,
+                "Failed to insert vein is_bedrock"
             );
             Err(e)
         }
     }
 }
+
+/// Searches saved by a user, in name order.
+pub async fn list_saved_searches(
+    connection: &mut DbConnection,
+    user_id: &str,
+) -> QueryResult<Vec<SavedSearch>> {
+    with_connection!(connection, |connection| {
+        saved_search::table
+            .filter(saved_search::user_id.eq(user_id))
+            .order(saved_search::name.asc())
+            .select(SavedSearch::as_select())
+            .load(connection)
+            .await
+    })
+}
+
+pub async fn insert_saved_search(
+    connection: &mut DbConnection,
+    user_id: &str,
+    name: &str,
+    query_string: &str,
+) -> QueryResult<SavedSearch> {
+    let saved = SavedSearch {
+        id: Uuid::new_v4().to_string(),
+        user_id: user_id.to_string(),
+        name: name.to_string(),
+        query_string: query_string.to_string(),
+        created_at: Some(chrono::Utc::now().naive_utc()),
+    };
+    with_connection!(connection, |connection| {
+        insert_into(saved_search::table)
+            .values((
+                saved_search::id.eq(&saved.id),
+                saved_search::user_id.eq(&saved.user_id),
+                saved_search::name.eq(&saved.name),
+                saved_search::query_string.eq(&saved.query_string),
+                saved_search::created_at.eq(saved.created_at),
+            ))
+            .execute(connection)
+            .await
+    })?;
+    tracing::debug!(user_id = %user_id, saved_search_id = %saved.id, "Inserted saved search");
+    Ok(saved)
+}
+
+/// Deletes a saved search of the user. Returns the number of deleted rows.
+pub async fn delete_saved_search(
+    connection: &mut DbConnection,
+    user_id: &str,
+    id: &str,
+) -> QueryResult<usize> {
+    with_connection!(connection, |connection| {
+        diesel::delete(
+            saved_search::table
+                .filter(saved_search::id.eq(id))
+                .filter(saved_search::user_id.eq(user_id)),
+        )
+        .execute(connection)
+        .await
+    })
+}
+
+pub async fn insert_favorite_vein(
+    connection: &mut DbConnection,
+    user_id: &str,
+    vein_id: &str,
+) -> QueryResult<usize> {
+    with_connection!(connection, |connection| {
+        insert_into(favorite_vein::table)
+            .values((
+                favorite_vein::id.eq(Uuid::new_v4().to_string()),
+                favorite_vein::user_id.eq(user_id),
+                favorite_vein::vein_id.eq(vein_id),
+            ))
+            .execute(connection)
+            .await
+    })
+}
+
+pub async fn delete_favorite_vein(
+    connection: &mut DbConnection,
+    user_id: &str,
+    vein_id: &str,
+) -> QueryResult<usize> {
+    with_connection!(connection, |connection| {
+        diesel::delete(
+            favorite_vein::table
+                .filter(favorite_vein::user_id.eq(user_id))
+                .filter(favorite_vein::vein_id.eq(vein_id)),
+        )
+        .execute(connection)
+        .await
+    })
+}
+
+/// Ids of the veins pinned by the user.
+pub async fn list_favorite_vein_ids(
+    connection: &mut DbConnection,
+    user_id: &str,
+) -> QueryResult<Vec<String>> {
+    with_connection!(connection, |connection| {
+        favorite_vein::table
+            .filter(favorite_vein::user_id.eq(user_id))
+            .select(favorite_vein::vein_id)
+            .load(connection)
+            .await
+    })
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+
+use diesel_async::pooled_connection::deadpool::PoolError;
+
+use crate::database::queries::VeinWithStatus;
+use crate::models::forms::SearchQuery;
+use crate::models::saved_search::SavedSearch;
+use crate::ores::VeinOre;
+
+pub mod memory;
+pub mod sql;
+
+pub use memory::InMemoryVeinRepository;
+pub use sql::SqlVeinRepository;
+
+/// Failure of a repository call, whichever storage is behind it.
+#[derive(Debug)]
+pub enum RepositoryError {
+    Database(diesel::result::Error),
+    Pool(PoolError),
+}
+
+pub type RepositoryResult<T> = Result<T, RepositoryError>;
+
+impl RepositoryError {
+    /// The row collides with an existing one (same id, username, ...).
+    pub fn is_unique_violation(&self) -> bool {
+        matches!(
+            self,
+            RepositoryError::Database(diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            ))
+        )
+    }
+
+    /// The row refers to one that does not exist (an unknown vein id, ...).
+    pub fn is_foreign_key_violation(&self) -> bool {
+        matches!(
+            self,
+            RepositoryError::Database(diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::ForeignKeyViolation,
+                _,
+            ))
+        )
+    }
+
+    /// Builds the error a database would return, for storages without one.
+    pub(crate) fn database(kind: diesel::result::DatabaseErrorKind, message: String) -> Self {
+        RepositoryError::Database(diesel::result::Error::DatabaseError(
+            kind,
+            Box::new(message),
+        ))
+    }
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepositoryError::Database(e) => write!(f, "Database error: {}", e),
+            RepositoryError::Pool(e) => write!(f, "Connection pool error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl From<diesel::result::Error> for RepositoryError {
+    fn from(error: diesel::result::Error) -> Self {
+        RepositoryError::Database(error)
+    }
+}
+
+impl From<PoolError> for RepositoryError {
+    fn from(error: PoolError) -> Self {
+        RepositoryError::Pool(error)
+    }
+}
+
+/// The statuses tracked for a vein. Each change is recorded, and the latest one counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VeinStatus {
+    Confirmation,
+    Depletion,
+    Revocation,
+    IsBedrock,
+}
+
+/// Number of veins yielding one material, as exported by `/metrics`. Revoked veins are not
+/// counted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VeinCount {
+    /// Lowercased material name
+    pub material: String,
+    pub total: usize,
+    pub confirmed: usize,
+    pub depleted: usize,
+}
+
+impl VeinCount {
+    /// Groups veins by the materials of their composition, most veins first, keeping at most
+    /// `limit` materials. Matches the SQL count.
+    pub fn tally<'a>(
+        veins: impl IntoIterator<Item = &'a VeinWithStatus>,
+        limit: usize,
+    ) -> Vec<VeinCount> {
+        let mut counts = std::collections::BTreeMap::<String, VeinCount>::new();
+        for vein in veins.into_iter().filter(|vein| !vein.revoked) {
+            let materials: std::collections::BTreeSet<String> = vein
+                .ores
+                .iter()
+                .map(|ore| ore.material.to_lowercase())
+                .collect();
+            for material in materials {
+                let count = counts.entry(material.clone()).or_insert_with(|| VeinCount {
+                    material,
+                    ..Default::default()
+                });
+                count.total += 1;
+                count.confirmed += usize::from(vein.confirmed);
+                count.depleted += usize::from(vein.depleted);
+            }
+        }
+        let mut counts: Vec<VeinCount> = counts.into_values().collect();
+        // 名前順に並んでいるので、安定ソートで同数の鉱石は名前順のまま残る
+        counts.sort_by_key(|count| std::cmp::Reverse(count.total));
+        counts.truncate(limit);
+        counts
+    }
+}
+
+/// One page of search results.
+#[derive(Clone, Default)]
+pub struct VeinPage {
+    pub veins: Vec<VeinWithStatus>,
+    /// Matching veins on all pages
+    pub total: usize,
+}
+
+/// Storage of veins and their statuses, used by the handlers through `AppState`.
+#[async_trait::async_trait]
+pub trait VeinRepository: Send + Sync {
+    /// Veins matching the query, with their latest statuses and note.
+    async fn search_veins(&self, query: &SearchQuery) -> RepositoryResult<Vec<VeinWithStatus>>;
+
+    /// The page of matching veins selected by `page` and `limit` of the query.
+    async fn search_veins_page(&self, query: &SearchQuery) -> RepositoryResult<VeinPage>;
+
+    /// Adds a vein. An empty note is not recorded.
+    async fn insert_vein(
+        &self,
+        id: &str,
+        name: &str,
+        x_coord: i32,
+        y_range: Option<(i32, i32)>,
+        z_coord: i32,
+        notes: &Option<String>,
+    ) -> RepositoryResult<()>;
+
+    /// Adds a vein together with its statuses, note and composition, all or nothing.
+    ///
+    /// The id is kept, and the creation time when set; otherwise the vein is created now.
+    async fn add_vein(&self, vein: &VeinWithStatus) -> RepositoryResult<()>;
+
+    /// Records a new value for one of the statuses of a vein.
+    async fn record_status(
+        &self,
+        vein_id: &str,
+        status: VeinStatus,
+        value: bool,
+    ) -> RepositoryResult<()>;
+
+    /// Vein counts of the materials yielding the most veins, at most `limit` of them.
+    async fn count_veins(&self, limit: usize) -> RepositoryResult<Vec<VeinCount>>;
+
+    /// Searches saved by the user, in name order.
+    async fn list_saved_searches(&self, user_id: &str) -> RepositoryResult<Vec<SavedSearch>>;
+
+    /// Saves a search under a name. A name the user already uses is a unique violation.
+    async fn save_search(
+        &self,
+        user_id: &str,
+        name: &str,
+        query_string: &str,
+    ) -> RepositoryResult<SavedSearch>;
+
+    /// Deletes a saved search of the user. Returns whether it existed.
+    async fn delete_saved_search(&self, user_id: &str, id: &str) -> RepositoryResult<bool>;
+
+    /// Pins a vein to the user's home page, or unpins it. Repeating either is not an error.
+    async fn set_favorite(
+        &self,
+        user_id: &str,
+        vein_id: &str,
+        favorite: bool,
+    ) -> RepositoryResult<()>;
+
+    /// Ids of the veins pinned by the user.
+    async fn favorite_vein_ids(&self, user_id: &str) -> RepositoryResult<Vec<String>>;
+
+    /// Replaces the ore composition of a vein. Repeated entries are stored once.
+    async fn set_composition(&self, vein_id: &str, ores: &[VeinOre]) -> RepositoryResult<()>;
+
+    /// Materials recorded in the compositions of veins, in name order.
+    async fn list_materials(&self) -> RepositoryResult<Vec<String>>;
+}
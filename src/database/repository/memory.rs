@@ -0,0 +1,316 @@
+use std::cmp::Ordering;
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use diesel::result::DatabaseErrorKind;
+use uuid::Uuid;
+
+use crate::database::queries::VeinWithStatus;
+use crate::database::repository::{
+    RepositoryError, RepositoryResult, VeinCount, VeinPage, VeinRepository, VeinStatus,
+};
+use crate::models::forms::{SearchQuery, SortKey, SortOrder};
+use crate::models::saved_search::SavedSearch;
+use crate::ores::{VeinOre, normalize_composition};
+
+/// Vein storage kept in memory, for tests and trying the app without a database.
+///
+/// Only the latest value of each status and the latest note are kept, so both note scopes of
+/// the free-text search look at the same note.
+#[derive(Clone, Default)]
+pub struct InMemoryVeinRepository {
+    veins: Arc<Mutex<Vec<VeinWithStatus>>>,
+    saved_searches: Arc<Mutex<Vec<SavedSearch>>>,
+    /// Pairs of user id and vein id
+    favorites: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl InMemoryVeinRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn veins(&self) -> std::sync::MutexGuard<'_, Vec<VeinWithStatus>> {
+        self.veins.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn saved_searches(&self) -> std::sync::MutexGuard<'_, Vec<SavedSearch>> {
+        self.saved_searches
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn favorites(&self) -> std::sync::MutexGuard<'_, Vec<(String, String)>> {
+        self.favorites.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Orders veins the way the SQL search does, with the id as the final tiebreaker.
+fn compare_veins(a: &VeinWithStatus, b: &VeinWithStatus, query: &SearchQuery) -> Ordering {
+    let ordering = match query.sort_key() {
+        SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        SortKey::Distance => a
+            .distance_squared_from(query.origin())
+            .cmp(&b.distance_squared_from(query.origin())),
+        SortKey::CreatedAt => a.created_at.cmp(&b.created_at),
+        SortKey::Status => a.status_rank().cmp(&b.status_rank()),
+    };
+    let ordering = match query.sort_order() {
+        SortOrder::Asc => ordering,
+        SortOrder::Desc => ordering.reverse(),
+    };
+    ordering.then_with(|| a.id.cmp(&b.id))
+}
+
+#[async_trait::async_trait]
+impl VeinRepository for InMemoryVeinRepository {
+    async fn search_veins(&self, query: &SearchQuery) -> RepositoryResult<Vec<VeinWithStatus>> {
+        let name_filter = query.get_name_filter().map(|name| name.to_lowercase());
+        let terms = query.text_terms();
+        let favorite_ids = match &query.favorites_of {
+            Some(user_id) => Some(self.favorite_vein_ids(user_id).await?),
+            None => None,
+        };
+
+        let mut veins: Vec<VeinWithStatus> = self
+            .veins()
+            .iter()
+            .filter(|vein| {
+                name_filter
+                    .as_ref()
+                    .is_none_or(|name| vein.name.to_lowercase().contains(name))
+            })
+            .filter(|vein| !vein.revoked || query.should_include_revoked())
+            .filter(|vein| {
+                let name = vein.name.to_lowercase();
+                let notes = vein.notes.as_deref().unwrap_or_default().to_lowercase();
+                terms
+                    .iter()
+                    .all(|term| name.contains(term) || notes.contains(term))
+            })
+            .filter(|vein| {
+                query
+                    .get_material_filter()
+                    .is_none_or(|material| vein.yields(material))
+            })
+            .filter(|vein| {
+                favorite_ids
+                    .as_ref()
+                    .is_none_or(|ids| ids.contains(&vein.id))
+            })
+            .filter(|vein| {
+                query
+                    .confirmed
+                    .is_none_or(|wanted| vein.confirmed == wanted)
+                    && query.depleted.is_none_or(|wanted| vein.depleted == wanted)
+                    && query.bedrock.is_none_or(|wanted| vein.is_bedrock == wanted)
+            })
+            .filter(|vein| {
+                // 登録日時のない鉱脈は期間を指定すると一致しない（SQL の NULL 比較と同じ）
+                query
+                    .created_since()
+                    .is_none_or(|since| vein.created_at.is_some_and(|at| at >= since))
+                    && query
+                        .created_until()
+                        .is_none_or(|until| vein.created_at.is_some_and(|at| at < until))
+            })
+            .filter(|vein| vein.reachable_within(query.y_from, query.y_to))
+            .cloned()
+            .collect();
+        veins.sort_by(|a, b| compare_veins(a, b, query));
+        Ok(veins)
+    }
+
+    async fn search_veins_page(&self, query: &SearchQuery) -> RepositoryResult<VeinPage> {
+        let veins = self.search_veins(query).await?;
+        let total = veins.len();
+        let veins = veins
+            .into_iter()
+            .skip(usize::try_from(query.offset()).unwrap_or(usize::MAX))
+            .take(query.limit() as usize)
+            .collect();
+        Ok(VeinPage { veins, total })
+    }
+
+    async fn insert_vein(
+        &self,
+        id: &str,
+        name: &str,
+        x_coord: i32,
+        y_range: Option<(i32, i32)>,
+        z_coord: i32,
+        notes: &Option<String>,
+    ) -> RepositoryResult<()> {
+        let mut veins = self.veins();
+        if veins.iter().any(|vein| vein.id == id) {
+            return Err(RepositoryError::database(
+                DatabaseErrorKind::UniqueViolation,
+                format!("Duplicate entry '{}' for key 'PRIMARY'", id),
+            ));
+        }
+
+        veins.push(VeinWithStatus {
+            id: id.to_string(),
+            name: name.to_string(),
+            x_coord,
+            y_min: y_range.map(|(min, _)| min),
+            y_max: y_range.map(|(_, max)| max),
+            z_coord,
+            notes: notes.clone().filter(|note| !note.is_empty()),
+            created_at: Some(Utc::now().naive_utc()),
+            confirmed: false,
+            depleted: false,
+            revoked: false,
+            is_bedrock: false,
+            ores: Vec::new(),
+        });
+        Ok(())
+    }
+
+    async fn add_vein(&self, vein: &VeinWithStatus) -> RepositoryResult<()> {
+        let mut veins = self.veins();
+        if veins.iter().any(|stored| stored.id == vein.id) {
+            return Err(RepositoryError::database(
+                DatabaseErrorKind::UniqueViolation,
+                format!("Duplicate entry '{}' for key 'PRIMARY'", vein.id),
+            ));
+        }
+
+        let mut stored = vein.clone();
+        stored.notes = stored.notes.filter(|note| !note.is_empty());
+        stored.created_at = stored.created_at.or(Some(Utc::now().naive_utc()));
+        normalize_composition(&mut stored.ores);
+        veins.push(stored);
+        Ok(())
+    }
+
+    async fn record_status(
+        &self,
+        vein_id: &str,
+        status: VeinStatus,
+        value: bool,
+    ) -> RepositoryResult<()> {
+        let mut veins = self.veins();
+        // データベースと同様に、存在しない鉱脈への記録は外部キー違反とする
+        let Some(vein) = veins.iter_mut().find(|vein| vein.id == vein_id) else {
+            return Err(RepositoryError::database(
+                DatabaseErrorKind::ForeignKeyViolation,
+                format!("Vein {} does not exist", vein_id),
+            ));
+        };
+
+        match status {
+            VeinStatus::Confirmation => vein.confirmed = value,
+            VeinStatus::Depletion => vein.depleted = value,
+            VeinStatus::Revocation => vein.revoked = value,
+            VeinStatus::IsBedrock => vein.is_bedrock = value,
+        }
+        Ok(())
+    }
+
+    async fn count_veins(&self, limit: usize) -> RepositoryResult<Vec<VeinCount>> {
+        Ok(VeinCount::tally(self.veins().iter(), limit))
+    }
+
+    async fn list_saved_searches(&self, user_id: &str) -> RepositoryResult<Vec<SavedSearch>> {
+        let mut searches: Vec<SavedSearch> = self
+            .saved_searches()
+            .iter()
+            .filter(|search| search.user_id == user_id)
+            .cloned()
+            .collect();
+        searches.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(searches)
+    }
+
+    async fn save_search(
+        &self,
+        user_id: &str,
+        name: &str,
+        query_string: &str,
+    ) -> RepositoryResult<SavedSearch> {
+        let mut searches = self.saved_searches();
+        if searches
+            .iter()
+            .any(|search| search.user_id == user_id && search.name == name)
+        {
+            return Err(RepositoryError::database(
+                DatabaseErrorKind::UniqueViolation,
+                format!("Duplicate entry '{}-{}' for key 'user_id'", user_id, name),
+            ));
+        }
+
+        let saved = SavedSearch {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            name: name.to_string(),
+            query_string: query_string.to_string(),
+            created_at: Some(Utc::now().naive_utc()),
+        };
+        searches.push(saved.clone());
+        Ok(saved)
+    }
+
+    async fn delete_saved_search(&self, user_id: &str, id: &str) -> RepositoryResult<bool> {
+        let mut searches = self.saved_searches();
+        let before = searches.len();
+        searches.retain(|search| !(search.user_id == user_id && search.id == id));
+        Ok(searches.len() < before)
+    }
+
+    async fn set_favorite(
+        &self,
+        user_id: &str,
+        vein_id: &str,
+        favorite: bool,
+    ) -> RepositoryResult<()> {
+        if favorite && !self.veins().iter().any(|vein| vein.id == vein_id) {
+            return Err(RepositoryError::database(
+                DatabaseErrorKind::ForeignKeyViolation,
+                format!("Vein {} does not exist", vein_id),
+            ));
+        }
+
+        let mut favorites = self.favorites();
+        favorites.retain(|(user, vein)| !(user == user_id && vein == vein_id));
+        if favorite {
+            favorites.push((user_id.to_string(), vein_id.to_string()));
+        }
+        Ok(())
+    }
+
+    async fn favorite_vein_ids(&self, user_id: &str) -> RepositoryResult<Vec<String>> {
+        Ok(self
+            .favorites()
+            .iter()
+            .filter(|(user, _)| user == user_id)
+            .map(|(_, vein)| vein.clone())
+            .collect())
+    }
+
+    async fn set_composition(&self, vein_id: &str, ores: &[VeinOre]) -> RepositoryResult<()> {
+        let mut veins = self.veins();
+        let Some(vein) = veins.iter_mut().find(|vein| vein.id == vein_id) else {
+            return Err(RepositoryError::database(
+                DatabaseErrorKind::ForeignKeyViolation,
+                format!("Vein {} does not exist", vein_id),
+            ));
+        };
+
+        let mut ores = ores.to_vec();
+        normalize_composition(&mut ores);
+        vein.ores = ores;
+        Ok(())
+    }
+
+    async fn list_materials(&self) -> RepositoryResult<Vec<String>> {
+        let mut materials: Vec<String> = self
+            .veins()
+            .iter()
+            .flat_map(|vein| vein.ores.iter().map(|ore| ore.material.clone()))
+            .collect();
+        materials.sort();
+        materials.dedup();
+        Ok(materials)
+    }
+}
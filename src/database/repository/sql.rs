@@ -0,0 +1,158 @@
+use crate::database::connection::DieselPool;
+use crate::database::queries::{
+    VeinWithStatus, count_veins_by_material, import_vein, insert_vein, insert_vein_confirmation,
+    insert_vein_depletion, insert_vein_is_bedrock, insert_vein_revocation, search_veins,
+    search_veins_page,
+};
+use crate::database::queries::{
+    delete_favorite_vein, delete_saved_search, insert_favorite_vein, insert_saved_search,
+    list_favorite_vein_ids, list_ore_materials, list_saved_searches, replace_vein_ores,
+};
+use crate::database::repository::{
+    RepositoryError, RepositoryResult, VeinCount, VeinPage, VeinRepository, VeinStatus,
+};
+use crate::models::forms::SearchQuery;
+use crate::models::saved_search::SavedSearch;
+use crate::ores::{VeinOre, normalize_composition};
+
+/// Vein storage in the database selected by `DATABASE_URL`.
+#[derive(Clone)]
+pub struct SqlVeinRepository {
+    pool: DieselPool,
+}
+
+impl SqlVeinRepository {
+    pub fn new(pool: DieselPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl VeinRepository for SqlVeinRepository {
+    async fn search_veins(&self, query: &SearchQuery) -> RepositoryResult<Vec<VeinWithStatus>> {
+        let mut connection = self.pool.get().await?;
+        Ok(search_veins(&mut connection, query).await?)
+    }
+
+    async fn search_veins_page(&self, query: &SearchQuery) -> RepositoryResult<VeinPage> {
+        let mut connection = self.pool.get().await?;
+        let (veins, total) = search_veins_page(&mut connection, query).await?;
+        Ok(VeinPage {
+            veins,
+            total: usize::try_from(total).unwrap_or_default(),
+        })
+    }
+
+    async fn insert_vein(
+        &self,
+        id: &str,
+        name: &str,
+        x_coord: i32,
+        y_range: Option<(i32, i32)>,
+        z_coord: i32,
+        notes: &Option<String>,
+    ) -> RepositoryResult<()> {
+        let mut connection = self.pool.get().await?;
+        insert_vein(&mut connection, id, name, x_coord, y_range, z_coord, notes).await?;
+        Ok(())
+    }
+
+    async fn add_vein(&self, vein: &VeinWithStatus) -> RepositoryResult<()> {
+        let mut connection = self.pool.get().await?;
+        Ok(import_vein(&mut connection, vein).await?)
+    }
+
+    async fn record_status(
+        &self,
+        vein_id: &str,
+        status: VeinStatus,
+        value: bool,
+    ) -> RepositoryResult<()> {
+        let mut connection = self.pool.get().await?;
+        let connection = &mut connection;
+        match status {
+            VeinStatus::Confirmation => insert_vein_confirmation(connection, vein_id, value).await,
+            VeinStatus::Depletion => insert_vein_depletion(connection, vein_id, value).await,
+            VeinStatus::Revocation => insert_vein_revocation(connection, vein_id, value).await,
+            VeinStatus::IsBedrock => insert_vein_is_bedrock(connection, vein_id, value).await,
+        }?;
+        Ok(())
+    }
+
+    async fn count_veins(&self, limit: usize) -> RepositoryResult<Vec<VeinCount>> {
+        let mut connection = self.pool.get().await?;
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let counts = count_veins_by_material(&mut connection, limit).await?;
+        Ok(counts
+            .into_iter()
+            .map(|count| VeinCount {
+                material: count.material,
+                total: count.total as usize,
+                confirmed: count.confirmed as usize,
+                depleted: count.depleted as usize,
+            })
+            .collect())
+    }
+
+    async fn list_saved_searches(&self, user_id: &str) -> RepositoryResult<Vec<SavedSearch>> {
+        let mut connection = self.pool.get().await?;
+        Ok(list_saved_searches(&mut connection, user_id).await?)
+    }
+
+    async fn save_search(
+        &self,
+        user_id: &str,
+        name: &str,
+        query_string: &str,
+    ) -> RepositoryResult<SavedSearch> {
+        let mut connection = self.pool.get().await?;
+        Ok(insert_saved_search(&mut connection, user_id, name, query_string).await?)
+    }
+
+    async fn delete_saved_search(&self, user_id: &str, id: &str) -> RepositoryResult<bool> {
+        let mut connection = self.pool.get().await?;
+        Ok(delete_saved_search(&mut connection, user_id, id).await? > 0)
+    }
+
+    async fn set_favorite(
+        &self,
+        user_id: &str,
+        vein_id: &str,
+        favorite: bool,
+    ) -> RepositoryResult<()> {
+        let mut connection = self.pool.get().await?;
+        if !favorite {
+            delete_favorite_vein(&mut connection, user_id, vein_id).await?;
+            return Ok(());
+        }
+        match insert_favorite_vein(&mut connection, user_id, vein_id).await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let e = RepositoryError::from(e);
+                // 既にお気に入りの場合はそのままにする
+                if e.is_unique_violation() {
+                    Ok(())
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    async fn favorite_vein_ids(&self, user_id: &str) -> RepositoryResult<Vec<String>> {
+        let mut connection = self.pool.get().await?;
+        Ok(list_favorite_vein_ids(&mut connection, user_id).await?)
+    }
+
+    async fn set_composition(&self, vein_id: &str, ores: &[VeinOre]) -> RepositoryResult<()> {
+        let mut ores = ores.to_vec();
+        normalize_composition(&mut ores);
+        let mut connection = self.pool.get().await?;
+        Ok(replace_vein_ores(&mut connection, vein_id, &ores).await?)
+    }
+
+    async fn list_materials(&self) -> RepositoryResult<Vec<String>> {
+        let mut connection = self.pool.get().await?;
+        Ok(list_ore_materials(&mut connection).await?)
+    }
+}
@@ -0,0 +1,235 @@
+use std::fmt;
+
+use axum::{
+    Json,
+    extract::{
+        Request,
+        rejection::{FormRejection, QueryRejection},
+    },
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tower_sessions::Session;
+
+use crate::auth::backend::AuthSession;
+use crate::database::repository::RepositoryError;
+use crate::templates::{ErrorTemplate, Layout};
+
+/// Message shown instead of the detail of an internal failure.
+const INTERNAL_ERROR_MESSAGE: &str =
+    "サーバー内部でエラーが発生しました。しばらくしてから再度お試しください。";
+
+/// Error returned by handlers and middleware.
+///
+/// The message of the user facing variants is shown as is. For the others the detail is
+/// only logged, and the client gets a generic message.
+#[derive(Debug)]
+pub enum AppError {
+    /// The request was understood but its input is invalid.
+    Validation(String),
+    /// Login is required.
+    Unauthorized(String),
+    /// Logged in, but not allowed to do this.
+    Forbidden(String),
+    NotFound(String),
+    PayloadTooLarge(String),
+    /// Storage failure. A unique violation is reported as a conflict.
+    Database(RepositoryError),
+    Session(tower_sessions::session::Error),
+    Internal(String),
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+/// What is left of an error once it has been turned into a response, so that
+/// [`render_error_pages`] can render it for the client.
+#[derive(Debug, Clone)]
+struct ErrorReport {
+    heading: &'static str,
+    message: String,
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Database(e) if e.is_unique_violation() => StatusCode::CONFLICT,
+            AppError::Database(_) | AppError::Session(_) | AppError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    fn heading(&self) -> &'static str {
+        match self.status() {
+            StatusCode::BAD_REQUEST | StatusCode::PAYLOAD_TOO_LARGE => "入力エラー",
+            StatusCode::UNAUTHORIZED => "ログインが必要です",
+            StatusCode::FORBIDDEN => "アクセスが拒否されました",
+            StatusCode::NOT_FOUND => "ページが見つかりません",
+            StatusCode::CONFLICT => "登録済みです",
+            _ => "サーバーエラー",
+        }
+    }
+
+    /// The message that may be shown to the client.
+    pub fn public_message(&self) -> String {
+        match self {
+            AppError::Validation(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::NotFound(message)
+            | AppError::PayloadTooLarge(message) => message.clone(),
+            AppError::Database(e) if e.is_unique_violation() => {
+                "同じデータが既に登録されています。".to_string()
+            }
+            AppError::Database(_) | AppError::Session(_) | AppError::Internal(_) => {
+                INTERNAL_ERROR_MESSAGE.to_string()
+            }
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Validation(message) => write!(f, "Validation error: {}", message),
+            AppError::Unauthorized(message) => write!(f, "Unauthorized: {}", message),
+            AppError::Forbidden(message) => write!(f, "Forbidden: {}", message),
+            AppError::NotFound(message) => write!(f, "Not found: {}", message),
+            AppError::PayloadTooLarge(message) => write!(f, "Payload too large: {}", message),
+            AppError::Database(e) => write!(f, "{}", e),
+            AppError::Session(e) => write!(f, "Session error: {}", e),
+            AppError::Internal(message) => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<RepositoryError> for AppError {
+    fn from(error: RepositoryError) -> Self {
+        AppError::Database(error)
+    }
+}
+
+impl From<tower_sessions::session::Error> for AppError {
+    fn from(error: tower_sessions::session::Error) -> Self {
+        AppError::Session(error)
+    }
+}
+
+// 読み取れなかった入力は axum の英語の説明ではなく、入力エラーとして返す
+// ハンドラーでは `WithRejection<Query<_>, AppError>` のように受け取る
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        tracing::debug!(error = %rejection.body_text(), "Rejected query string");
+        AppError::Validation(
+            "検索条件を読み取れませんでした。入力内容を確認してください。".to_string(),
+        )
+    }
+}
+
+impl From<FormRejection> for AppError {
+    fn from(rejection: FormRejection) -> Self {
+        tracing::debug!(error = %rejection.body_text(), "Rejected form");
+        if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return AppError::PayloadTooLarge("リクエストが大きすぎます".to_string());
+        }
+        AppError::Validation(
+            "入力内容を読み取れませんでした。入力内容を確認してください。".to_string(),
+        )
+    }
+}
+
+impl From<askama::Error> for AppError {
+    fn from(error: askama::Error) -> Self {
+        AppError::Internal(format!("Failed to render template: {}", error))
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        // 内部の詳細はログにだけ残す
+        if status.is_server_error() {
+            tracing::error!(error = %self, "Internal error");
+        }
+
+        let report = ErrorReport {
+            heading: self.heading(),
+            message: self.public_message(),
+        };
+        let mut response = (status, report.message.clone()).into_response();
+        response.extensions_mut().insert(report);
+        response
+    }
+}
+
+// エラー応答を HTML のエラーページか JSON に描き直すミドルウェア
+// 本文の形式はリクエストの Accept ヘッダーで決める
+pub async fn render_error_pages(
+    auth_session: AuthSession,
+    session: Session,
+    request: Request,
+    next: Next,
+) -> Response {
+    let wants_json = wants_json(request.headers());
+    let response = next.run(request).await;
+
+    let Some(report) = response.extensions().get::<ErrorReport>().cloned() else {
+        return response;
+    };
+    let status = response.status();
+
+    if wants_json {
+        return (
+            status,
+            Json(serde_json::json!({
+                "status": status.as_u16(),
+                "error": report.message,
+            })),
+        )
+            .into_response();
+    }
+
+    let page = match Layout::load(&session, auth_session.user.as_ref()).await {
+        Ok(layout) => askama::Template::render(&ErrorTemplate {
+            layout,
+            heading: report.heading.to_string(),
+            message: report.message.clone(),
+        }),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to load layout for error page");
+            return response;
+        }
+    };
+
+    match page {
+        Ok(html) => (status, axum::response::Html(html)).into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to render error page");
+            response
+        }
+    }
+}
+
+/// Whether the client asked for JSON rather than a page.
+fn wants_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| {
+            let json = accept.find("application/json");
+            let html = accept.find("text/html");
+            match (json, html) {
+                (Some(json), Some(html)) => json < html,
+                (Some(_), None) => true,
+                _ => false,
+            }
+        })
+}
@@ -1,4 +1,8 @@
 pub mod auth;
+pub mod health;
+pub mod materials;
+pub mod saved_searches;
+pub mod sessions;
 pub mod static_files;
 pub mod vein;
 pub mod web;
@@ -0,0 +1,29 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+
+use crate::database::connection::AppState;
+
+/// Liveness probe: answers as long as the process serves requests.
+pub async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Readiness probe: the database answers and its migrations match the binary.
+///
+/// The migrations are compared at most once a minute, see [`MigrationStatus`](crate::database::migrations::MigrationStatus).
+/// Answers 503 otherwise, so that the supervisor keeps traffic away.
+pub async fn readyz(State(state): State<AppState>) -> Response {
+    if let Err(e) = state.diesel_pool.ping().await {
+        tracing::warn!(error = %e, "Readiness check failed: database unreachable");
+        return (StatusCode::SERVICE_UNAVAILABLE, "database unreachable").into_response();
+    }
+    if let Err(e) = state.migration_status.check().await {
+        tracing::warn!(error = %e, "Readiness check failed: migrations out of date");
+        return (StatusCode::SERVICE_UNAVAILABLE, "migrations not applied").into_response();
+    }
+
+    "ready".into_response()
+}
@@ -0,0 +1,33 @@
+use axum::{
+    extract::{Query, State},
+    response::Html,
+};
+use axum_extra::extract::WithRejection;
+use tower_sessions::Session;
+
+use crate::{
+    auth::backend::AuthSession,
+    database::connection::AppState,
+    error::{AppError, AppResult},
+    models::forms::SearchQuery,
+    ores::{large_veins, material_index},
+    templates::{Layout, MaterialsTemplate, render},
+};
+
+// 鉱石の索引。GT6 の鉱脈の定義と、記録された構成に含まれる鉱石を一覧する
+pub async fn materials_page(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    session: Session,
+    WithRejection(Query(params), _): WithRejection<Query<SearchQuery>, AppError>,
+) -> AppResult<Html<String>> {
+    let recorded = state.vein_repository.list_materials().await?;
+
+    let layout = Layout::load(&session, auth_session.user.as_ref()).await?;
+    render(&MaterialsTemplate {
+        layout,
+        materials: material_index(large_veins(), recorded),
+        from_x: params.from_x,
+        from_z: params.from_z,
+    })
+}
@@ -0,0 +1,118 @@
+use axum::{
+    Form,
+    extract::{Path, State},
+    response::Redirect,
+};
+use axum_extra::extract::WithRejection;
+use serde::Deserialize;
+use tower_sessions::Session;
+
+use crate::{
+    auth::backend::AuthSession,
+    database::connection::AppState,
+    error::{AppError, AppResult},
+    models::forms::SearchQuery,
+    templates::{FlashLevel, push_flash},
+};
+
+/// Longest accepted name of a saved search, as stored in `saved_search.name`.
+const MAX_NAME_LENGTH: usize = 100;
+/// Longest accepted query string, as stored in `saved_search.query_string`.
+const MAX_QUERY_LENGTH: usize = 2048;
+
+#[derive(Debug, Deserialize)]
+pub struct SaveSearchForm {
+    name: String,
+    query_state: Option<String>,
+}
+
+// 検索結果のページから、現在の検索条件に名前を付けて保存する
+pub async fn save_search_handler(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    session: Session,
+    WithRejection(Form(form), _): WithRejection<Form<SaveSearchForm>, AppError>,
+) -> AppResult<Redirect> {
+    let user = auth_session
+        .user
+        .ok_or_else(|| AppError::Unauthorized("ログインが必要です".to_string()))?;
+
+    let query: SearchQuery =
+        serde_urlencoded::from_str(form.query_state.as_deref().unwrap_or_default())
+            .map_err(|_| AppError::Validation("検索条件が正しくありません".to_string()))?;
+    // ページ番号は保存しない
+    let query_string = SearchQuery {
+        page: None,
+        ..query
+    }
+    .get_all_query_string();
+    let back = format!("/search?{}", query_string);
+
+    let name = form.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        push_flash(
+            &session,
+            FlashLevel::Error,
+            format!("検索の名前は1〜{}文字で入力してください", MAX_NAME_LENGTH),
+        )
+        .await?;
+        return Ok(Redirect::to(&back));
+    }
+    if query_string.len() > MAX_QUERY_LENGTH {
+        return Err(AppError::Validation(
+            "保存するには検索条件が長すぎます".to_string(),
+        ));
+    }
+
+    match state
+        .vein_repository
+        .save_search(&user.id, name, &query_string)
+        .await
+    {
+        Ok(saved) => {
+            tracing::info!(user_id = %user.id, saved_search_id = %saved.id, "Search saved");
+            push_flash(
+                &session,
+                FlashLevel::Success,
+                format!("検索「{}」を保存しました", name),
+            )
+            .await?;
+        }
+        Err(e) if e.is_unique_violation() => {
+            push_flash(
+                &session,
+                FlashLevel::Error,
+                format!("「{}」という名前の検索は既に保存されています", name),
+            )
+            .await?;
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(Redirect::to(&back))
+}
+
+pub async fn delete_saved_search_handler(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    session: Session,
+    Path(id): Path<String>,
+) -> AppResult<Redirect> {
+    let user = auth_session
+        .user
+        .ok_or_else(|| AppError::Unauthorized("ログインが必要です".to_string()))?;
+
+    // 他の利用者の保存した検索は見つからないものとして扱う
+    if !state
+        .vein_repository
+        .delete_saved_search(&user.id, &id)
+        .await?
+    {
+        return Err(AppError::NotFound(
+            "指定された検索が見つかりません".to_string(),
+        ));
+    }
+    tracing::info!(user_id = %user.id, saved_search_id = %id, "Saved search deleted");
+
+    push_flash(&session, FlashLevel::Success, "保存した検索を削除しました").await?;
+    Ok(Redirect::to("/"))
+}
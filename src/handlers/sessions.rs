@@ -0,0 +1,161 @@
+use std::net::SocketAddr;
+
+use axum::{
+    Form,
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, header},
+    middleware::Next,
+    response::{Html, Redirect, Response},
+};
+use axum_extra::extract::WithRejection;
+use serde::Deserialize;
+use tower_sessions::Session;
+
+use crate::{
+    auth::backend::AuthSession,
+    auth::session_store::DieselSessionStore,
+    database::connection::AppState,
+    error::{AppError, AppResult},
+    models::sessions::{ActiveSession, session_handle},
+    templates::{FlashLevel, Layout, SessionsTemplate, push_flash, render},
+};
+
+fn session_store_error(e: tower_sessions::session_store::Error) -> AppError {
+    AppError::Internal(format!("Session store error: {}", e))
+}
+
+// ログイン中のリクエストについて、端末（User-Agent）・IP・最終アクセス日時を記録するミドルウェア
+pub async fn record_session_activity(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    session: Session,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // ログイン直後はセッション ID がまだ保存されていないため、次のリクエストで記録される
+    // 間隔内に記録済みのセッションはデータベースに問い合わせない
+    if let (Some(user), Some(id)) = (&auth_session.user, session.id())
+        && state.recent_activity.is_due(&id)
+    {
+        let user_agent = user_agent(request.headers());
+        let ip = state.config.client_ip(request.headers(), addr);
+        let store = DieselSessionStore::new(state.diesel_pool.clone());
+        if let Err(e) = store
+            .record_activity(&id, &user.id, user_agent, &ip.to_string())
+            .await
+        {
+            // 記録に失敗してもリクエストは続ける
+            tracing::warn!(error = %e, "Failed to record session activity");
+        }
+    }
+
+    next.run(request).await
+}
+
+fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .filter(|agent| !agent.is_empty())
+}
+
+// ログイン中の端末一覧
+pub async fn sessions_page(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    session: Session,
+) -> AppResult<Html<String>> {
+    let user = auth_session
+        .user
+        .as_ref()
+        .ok_or_else(|| AppError::Unauthorized("ログインが必要です".to_string()))?;
+    let current_id = session.id().map(|id| id.to_string());
+
+    let sessions = DieselSessionStore::new(state.diesel_pool.clone())
+        .list_user_sessions(&user.id)
+        .await
+        .map_err(session_store_error)?
+        .into_iter()
+        .map(|row| ActiveSession::new(row, current_id.as_deref()))
+        .collect();
+
+    let layout = Layout::load(&session, Some(user)).await?;
+    render(&SessionsTemplate { layout, sessions })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeSessionForm {
+    handle: String,
+}
+
+// 指定した端末をログアウトさせる
+pub async fn revoke_session_handler(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    session: Session,
+    WithRejection(Form(form), _): WithRejection<Form<RevokeSessionForm>, AppError>,
+) -> AppResult<Redirect> {
+    let user = auth_session
+        .user
+        .ok_or_else(|| AppError::Unauthorized("ログインが必要です".to_string()))?;
+    let store = DieselSessionStore::new(state.diesel_pool.clone());
+
+    let target = store
+        .list_user_sessions(&user.id)
+        .await
+        .map_err(session_store_error)?
+        .into_iter()
+        .find(|row| session_handle(&row.id) == form.handle)
+        .ok_or_else(|| AppError::NotFound("指定されたセッションが見つかりません".to_string()))?;
+
+    // 現在のセッションは応答時に保存し直されるため、ログアウトで終了させる
+    if session.id().is_some_and(|id| id.to_string() == target.id) {
+        return Err(AppError::Validation(
+            "この端末のセッションはログアウトで終了してください".to_string(),
+        ));
+    }
+
+    store
+        .delete_user_session(&user.id, &target.id)
+        .await
+        .map_err(session_store_error)?;
+    tracing::info!(user_id = %user.id, handle = %form.handle, "Session revoked");
+
+    push_flash(
+        &session,
+        FlashLevel::Success,
+        "選択した端末をログアウトしました",
+    )
+    .await?;
+    Ok(Redirect::to("/auth/sessions"))
+}
+
+// この端末以外をすべてログアウトさせる
+pub async fn revoke_other_sessions_handler(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    session: Session,
+) -> AppResult<Redirect> {
+    let user = auth_session
+        .user
+        .ok_or_else(|| AppError::Unauthorized("ログインが必要です".to_string()))?;
+    let current_id = session
+        .id()
+        .map(|id| id.to_string())
+        .ok_or_else(|| AppError::Internal("Logged in without a stored session".to_string()))?;
+
+    let deleted = DieselSessionStore::new(state.diesel_pool.clone())
+        .delete_other_user_sessions(&user.id, &current_id)
+        .await
+        .map_err(session_store_error)?;
+    tracing::info!(user_id = %user.id, deleted, "Other sessions revoked");
+
+    push_flash(
+        &session,
+        FlashLevel::Success,
+        format!("他の端末のセッションを{}件ログアウトしました", deleted),
+    )
+    .await?;
+    Ok(Redirect::to("/auth/sessions"))
+}
@@ -1,41 +1,150 @@
-use axum::{http::StatusCode, response::Html};
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
 
-pub async fn serve_index() -> Html<String> {
-    match tokio::fs::read_to_string("/home/latte/gt6-vein-manager/public/index.html").await {
-        Ok(content) => Html(content),
-        Err(_) => Html(generate_error_html("index.html が見つかりませんでした。")),
-    }
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use rust_embed::RustEmbed;
+
+use crate::database::connection::AppState;
+use crate::error::AppError;
+
+/// `public/` ディレクトリをコンパイル時にバイナリへ埋め込む
+#[derive(RustEmbed)]
+#[folder = "public/"]
+struct PublicAssets;
+
+/// Cache lifetime for embedded assets; they only change with a new binary.
+const EMBEDDED_MAX_AGE_SECONDS: u32 = 60 * 60;
+
+/// Serves files from `public/`, either embedded in the binary or, for development,
+/// from an override directory on disk.
+#[derive(Clone, Debug, Default)]
+pub struct StaticAssets {
+    override_dir: Option<PathBuf>,
+}
+
+struct Asset {
+    data: Cow<'static, [u8]>,
+    etag: String,
+    cache_control: String,
 }
 
-pub async fn serve_css() -> (StatusCode, [(&'static str, &'static str); 1], String) {
-    match tokio::fs::read_to_string("/home/latte/gt6-vein-manager/public/styles.css").await {
-        Ok(content) => (StatusCode::OK, [("content-type", "text/css")], content),
-        Err(_) => (
-            StatusCode::NOT_FOUND,
-            [("content-type", "text/plain")],
-            "CSS file not found".to_string(),
-        ),
+impl StaticAssets {
+    pub fn new(override_dir: Option<PathBuf>) -> Self {
+        if let Some(dir) = &override_dir {
+            tracing::info!(
+                directory = %dir.display(),
+                "Serving static files from override directory"
+            );
+        }
+        Self { override_dir }
     }
+
+    async fn load(&self, path: &str) -> Option<Asset> {
+        if !is_safe_path(path) {
+            return None;
+        }
+
+        if let Some(dir) = &self.override_dir {
+            let file_path = dir.join(path);
+            let metadata = tokio::fs::metadata(&file_path).await.ok()?;
+            if !metadata.is_file() {
+                return None;
+            }
+            let data = tokio::fs::read(&file_path).await.ok()?;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default();
+
+            // 開発用なので毎回再検証させる
+            return Some(Asset {
+                data: Cow::Owned(data),
+                etag: format!("\"{:x}-{:x}\"", modified, metadata.len()),
+                cache_control: "no-cache".to_string(),
+            });
+        }
+
+        let file = PublicAssets::get(path)?;
+        let hash: String = file
+            .metadata
+            .sha256_hash()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        Some(Asset {
+            data: file.data,
+            etag: format!("\"{}\"", hash),
+            cache_control: format!("public, max-age={}", EMBEDDED_MAX_AGE_SECONDS),
+        })
+    }
+
+    async fn respond(&self, path: &str, headers: &HeaderMap) -> Response {
+        let Some(asset) = self.load(path).await else {
+            return AppError::NotFound("ファイルが見つかりません".to_string()).into_response();
+        };
+
+        let etag = HeaderValue::from_str(&asset.etag).expect("ETag is always ASCII");
+        let cache_control =
+            HeaderValue::from_str(&asset.cache_control).expect("Cache-Control is always ASCII");
+
+        let not_modified = headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                value
+                    .split(',')
+                    .any(|tag| tag.trim() == asset.etag || tag.trim() == "*")
+            });
+        if not_modified {
+            return (
+                StatusCode::NOT_MODIFIED,
+                [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control)],
+            )
+                .into_response();
+        }
+
+        let content_type = mime_guess::from_path(path).first_or_octet_stream();
+        let content_type = HeaderValue::from_str(content_type.essence_str())
+            .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+
+        (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::ETAG, etag),
+                (header::CACHE_CONTROL, cache_control),
+            ],
+            asset.data.into_owned(),
+        )
+            .into_response()
+    }
+}
+
+/// Rejects absolute paths and `..` so the override directory cannot be escaped.
+fn is_safe_path(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+pub async fn serve_css(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    state.static_assets.respond("styles.css", &headers).await
 }
 
-fn generate_error_html(message: &str) -> String {
-    format!(
-        r#"
-        <!DOCTYPE html>
-        <html lang="ja">
-        <head>
-            <meta charset="UTF-8">
-            <title>エラー</title>
-            <link rel="stylesheet" href="styles.css">
-        </head>
-        <body class="error-page">
-            <h1>エラー</h1>
-            <div class="error">
-                <p>{}</p>
-            </div>
-        </body>
-        </html>
-        "#,
-        message
-    )
+// `/static/` 以下の任意のファイル（JS・画像など）
+pub async fn serve_static(
+    State(state): State<AppState>,
+    UrlPath(path): UrlPath<String>,
+    headers: HeaderMap,
+) -> Response {
+    state.static_assets.respond(&path, &headers).await
 }
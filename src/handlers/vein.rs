@@ -1,26 +1,31 @@
+use crate::auth::backend::AuthSession;
 use crate::database::connection::AppState;
-use crate::database::queries::{
-    insert_vein_confirmation, insert_vein_depletion, insert_vein_is_bedrock, insert_vein_revocation,
-};
+use crate::database::repository::VeinStatus;
+use crate::error::{AppError, AppResult};
 use axum::{
     Form,
     extract::{Path, State},
-    http::StatusCode,
     response::Redirect,
 };
-use diesel_async::AsyncMysqlConnection;
+use axum_extra::extract::WithRejection;
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
 pub struct VeinButtonForm {
     query_state: Option<String>,
+    /// Set by the buttons on the home page to return there instead of to the search
+    return_home: Option<bool>,
 }
 
 impl VeinButtonForm {
     fn build_redirect_url(&self) -> String {
+        if self.return_home.unwrap_or(false) {
+            return "/".to_string();
+        }
         let mut url = String::from("/search");
         if let Some(query_state) = &self.query_state {
-            if !query_state.is_empty() {
+            // 改行などの制御文字を含む値はリダイレクト先に使わない
+            if !query_state.is_empty() && !query_state.chars().any(char::is_control) {
                 url.push_str(&format!("?{}", query_state));
             }
         }
@@ -28,82 +33,100 @@ impl VeinButtonForm {
     }
 }
 
-#[derive(Debug)]
-pub enum Action {
-    Confirmation,
-    Depletion,
-    Revocation,
-    IsBedrock,
-}
-
 async fn handle_vein_action(
-    _state: AppState,
+    state: AppState,
     vein_id: String,
     form: VeinButtonForm,
-    connection: &mut AsyncMysqlConnection,
-    action: Action,
+    action: VeinStatus,
     status: bool,
-) -> Result<Redirect, StatusCode> {
-    let result = match action {
-        Action::Confirmation => insert_vein_confirmation(connection, &vein_id, status).await,
-        Action::Depletion => insert_vein_depletion(connection, &vein_id, status).await,
-        Action::Revocation => insert_vein_revocation(connection, &vein_id, status).await,
-        Action::IsBedrock => insert_vein_is_bedrock(connection, &vein_id, status).await,
-    };
+) -> AppResult<Redirect> {
+    let result = state
+        .vein_repository
+        .record_status(&vein_id, action, status)
+        .await;
 
     match result {
         Ok(_) => {
-            println!(
-                "Vein action '{}' for vein ID '{}' was successful.",
-                match action {
-                    Action::Confirmation => "Confirmation",
-                    Action::Depletion => "Depletion",
-                    Action::Revocation => "Revocation",
-                    Action::IsBedrock => "IsBedrock",
-                },
-                vein_id
-            );
+            tracing::info!(action = ?action, vein_id = %vein_id, value = status, "Recorded vein status");
             Ok(Redirect::to(&form.build_redirect_url()))
         }
-        Err(_) => {
-            eprintln!(
-                "Failed to perform action '{}' for vein ID '{}'.",
-                match action {
-                    Action::Confirmation => "Confirmation",
-                    Action::Depletion => "Depletion",
-                    Action::Revocation => "Revocation",
-                    Action::IsBedrock => "IsBedrock",
-                },
-                vein_id
-            );
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        Err(e) => {
+            tracing::warn!(action = ?action, vein_id = %vein_id, error = %e, "Failed to record vein status");
+            // 存在しない鉱脈への操作は外部キー違反になる
+            if e.is_foreign_key_violation() {
+                return Err(AppError::NotFound(
+                    "指定された鉱脈が見つかりません".to_string(),
+                ));
+            }
+            Err(e.into())
+        }
+    }
+}
+
+// お気に入りは利用者ごとに記録する
+async fn handle_favorite_action(
+    state: AppState,
+    auth_session: AuthSession,
+    vein_id: String,
+    form: VeinButtonForm,
+    favorite: bool,
+) -> AppResult<Redirect> {
+    let user = auth_session
+        .user
+        .ok_or_else(|| AppError::Unauthorized("ログインが必要です".to_string()))?;
+
+    match state
+        .vein_repository
+        .set_favorite(&user.id, &vein_id, favorite)
+        .await
+    {
+        Ok(()) => {
+            tracing::info!(user_id = %user.id, vein_id = %vein_id, favorite, "Recorded favorite vein");
+            Ok(Redirect::to(&form.build_redirect_url()))
         }
+        Err(e) if e.is_foreign_key_violation() => Err(AppError::NotFound(
+            "指定された鉱脈が見つかりません".to_string(),
+        )),
+        Err(e) => Err(e.into()),
     }
 }
 
+pub async fn vein_favorite_set(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(vein_id): Path<String>,
+    WithRejection(Form(form), _): WithRejection<Form<VeinButtonForm>, AppError>,
+) -> AppResult<Redirect> {
+    handle_favorite_action(state, auth_session, vein_id, form, true).await
+}
+
+pub async fn vein_favorite_revoke(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(vein_id): Path<String>,
+    WithRejection(Form(form), _): WithRejection<Form<VeinButtonForm>, AppError>,
+) -> AppResult<Redirect> {
+    handle_favorite_action(state, auth_session, vein_id, form, false).await
+}
+
 macro_rules! define_vein_action {
     ($func_name:ident, $action:expr, $status:expr) => {
         pub async fn $func_name(
             State(state): State<AppState>,
             Path(vein_id): Path<String>,
-            Form(form): Form<VeinButtonForm>,
-        ) -> Result<Redirect, StatusCode> {
-            let mut connection = state
-                .diesel_pool
-                .get()
-                .await
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-            handle_vein_action(state, vein_id, form, &mut connection, $action, $status).await
+            WithRejection(Form(form), _): WithRejection<Form<VeinButtonForm>, AppError>,
+        ) -> AppResult<Redirect> {
+            handle_vein_action(state, vein_id, form, $action, $status).await
         }
     };
 }
 
 // 定義されたマクロを使って関数を生成
-define_vein_action!(vein_confirmation_set, Action::Confirmation, true);
-define_vein_action!(vein_confirmation_revoke, Action::Confirmation, false);
-define_vein_action!(vein_depletion_set, Action::Depletion, true);
-define_vein_action!(vein_depletion_revoke, Action::Depletion, false);
-define_vein_action!(vein_revocation_set, Action::Revocation, true);
-define_vein_action!(vein_revocation_revoke, Action::Revocation, false);
-define_vein_action!(vein_is_bedrock_set, Action::IsBedrock, true);
-define_vein_action!(vein_is_bedrock_revoke, Action::IsBedrock, false);
+define_vein_action!(vein_confirmation_set, VeinStatus::Confirmation, true);
+define_vein_action!(vein_confirmation_revoke, VeinStatus::Confirmation, false);
+define_vein_action!(vein_depletion_set, VeinStatus::Depletion, true);
+define_vein_action!(vein_depletion_revoke, VeinStatus::Depletion, false);
+define_vein_action!(vein_revocation_set, VeinStatus::Revocation, true);
+define_vein_action!(vein_revocation_revoke, VeinStatus::Revocation, false);
+define_vein_action!(vein_is_bedrock_set, VeinStatus::IsBedrock, true);
+define_vein_action!(vein_is_bedrock_revoke, VeinStatus::IsBedrock, false);
@@ -1,7 +1,13 @@
 pub mod app;
 pub mod auth;
+pub mod commands;
 pub mod config;
 pub mod database;
+pub mod error;
 pub mod handlers;
+pub mod logging;
+pub mod metrics;
 pub mod models;
+pub mod ores;
 pub mod schema;
+pub mod templates;
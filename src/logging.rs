@@ -0,0 +1,86 @@
+use std::time::Instant;
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use serde::Deserialize;
+use tracing::{Instrument, field};
+use tracing_subscriber::EnvFilter;
+
+use crate::auth::backend::AuthSession;
+use crate::config::AppConfig;
+
+/// Query parameters whose values never appear in the logs.
+const SENSITIVE_PARAMETERS: &[&str] = &["token", "password", "csrf_token", "email"];
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+/// Installs the global logger. Logs go to standard error so that `export` can write to standard output.
+pub fn init(config: &AppConfig) -> anyhow::Result<()> {
+    let filter = EnvFilter::try_new(&config.log_level)
+        .map_err(|e| anyhow::anyhow!("Invalid log level {:?}: {}", config.log_level, e))?;
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+
+    let result = match config.log_format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).try_init(),
+    };
+    result.map_err(|e| anyhow::anyhow!("Failed to install the logger: {}", e))
+}
+
+// リクエストごとのスパンを作り、完了時にステータスと処理時間を記録するミドルウェア
+// ユーザー ID を参照するため認証レイヤーの内側に置く
+pub async fn trace_requests(auth_session: AuthSession, request: Request, next: Next) -> Response {
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = %request.uri().path(),
+        query = field::Empty,
+        user_id = field::Empty,
+    );
+    if let Some(query) = request.uri().query() {
+        span.record("query", redact_query(query));
+    }
+    if let Some(user) = &auth_session.user {
+        span.record("user_id", user.id.as_str());
+    }
+
+    let started = Instant::now();
+    let response = next.run(request).instrument(span.clone()).await;
+    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+    let status = response.status().as_u16();
+
+    span.in_scope(|| {
+        if response.status().is_server_error() {
+            tracing::error!(status, latency_ms, "request failed");
+        } else if response.status().is_client_error() {
+            tracing::warn!(status, latency_ms, "request rejected");
+        } else {
+            tracing::info!(status, latency_ms, "request completed");
+        }
+    });
+    response
+}
+
+/// Replaces the values of sensitive parameters, e.g. the invitation token of `/auth/register`.
+pub fn redact_query(query: &str) -> String {
+    query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, _)) if SENSITIVE_PARAMETERS.contains(&key) => {
+                format!("{}={}", key, REDACTED)
+            }
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
@@ -1,22 +1,155 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use clap::Parser;
 use gt6_vein_manager::{
-    app::create_app, config::AppConfig, database::connection::AppState,
+    app::create_app,
+    auth::repository::SqlAuthRepository,
+    auth::session_store::{DieselSessionStore, RecentActivity},
+    auth::throttle::LoginThrottle,
+    commands::{self, Command},
+    config::{AppConfig, ConfigArgs},
+    database::connection::AppState,
     database::connection::create_diesel_pool,
+    database::migrations::{MigrationStatus, prepare_database},
+    database::repository::SqlVeinRepository,
+    handlers::static_files::StaticAssets,
+    logging,
+    metrics::{Metrics, create_metrics_app},
 };
 
+/// GT6 鉱脈マネージャー
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let config = AppConfig::from_env()?;
+    // .env の値も環境変数として扱う
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
+    let config = Arc::new(AppConfig::load(cli.config)?);
+    logging::init(&config)?;
+
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(config).await,
+        command => commands::run(command, &config).await,
+    }
+}
+
+async fn serve(config: Arc<AppConfig>) -> anyhow::Result<()> {
+    // スキーマがバイナリより新しい場合はここで起動を中止する
+    prepare_database(&config.database_url, config.auto_migrate).await?;
+
+    let diesel_pool = create_diesel_pool(&config).await?;
+    let state = AppState {
+        config: config.clone(),
+        vein_repository: Arc::new(SqlVeinRepository::new(diesel_pool.clone())),
+        auth_repository: Arc::new(SqlAuthRepository::new(diesel_pool.clone())),
+        diesel_pool,
+        migration_status: MigrationStatus::prepared(&config.database_url),
+        login_throttle: LoginThrottle::new(),
+        recent_activity: RecentActivity::new(),
+        static_assets: StaticAssets::new(config.static_dir.clone()),
+        metrics: Metrics::new(),
+    };
+
+    // 終了シグナルをメトリクス用のサーバーにも伝える
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
 
-    let diesel_pool = create_diesel_pool().await?;
-    let state = AppState { diesel_pool };
+    // Prometheus からはログインなしで取得できるよう、別のアドレスでも公開する
+    let metrics_server = match &config.metrics_address {
+        Some(metrics_address) => {
+            let listener = tokio::net::TcpListener::bind(metrics_address).await?;
+            tracing::info!(address = %metrics_address, "Serving metrics");
+            let metrics_app = create_metrics_app(state.clone());
+            Some(tokio::spawn(async move {
+                if let Err(e) = axum::serve(listener, metrics_app)
+                    .with_graceful_shutdown(wait_for_shutdown(shutdown_rx))
+                    .await
+                {
+                    tracing::error!(error = %e, "Metrics server stopped");
+                }
+            }))
+        }
+        None => None,
+    };
 
+    let diesel_pool = state.diesel_pool.clone();
     let app = create_app(state).await?;
 
+    // 期限切れのセッションを定期的に削除する
+    let session_purge = DieselSessionStore::new(diesel_pool.clone()).spawn_expired_deletion(
+        std::time::Duration::from_secs(config.session_cleanup_interval_minutes * 60),
+    );
+
     let addr = config.server_address();
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    println!("Server running on http://{}", addr);
+    tracing::info!(address = %addr, public_url = %config.public_url, "Server running");
+
+    // クライアントIPをログイン試行制限に使うため接続情報を付与する
+    // 終了シグナルを受けたら新しい接続を止め、処理中のリクエストが終わるのを待つ
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        let _ = shutdown_tx.send(true);
+    })
+    .await?;
 
-    axum::serve(listener, app).await?;
+    if let Some(metrics_server) = metrics_server
+        && let Err(e) = metrics_server.await
+    {
+        tracing::error!(error = %e, "Metrics server task failed");
+    }
+    session_purge.stop().await;
+    diesel_pool.close();
+    tracing::info!("Server stopped");
 
     Ok(())
 }
+
+/// Resolves once the main server has received a shutdown signal.
+async fn wait_for_shutdown(mut shutdown_rx: tokio::sync::watch::Receiver<bool>) {
+    // 送信側が先に破棄された場合も終了扱いにする
+    let _ = shutdown_rx.wait_for(|stopping| *stopping).await;
+}
+
+/// Resolves on Ctrl+C (SIGINT) or, on Unix, SIGTERM from the process supervisor.
+async fn shutdown_signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!(error = %e, "Failed to listen for Ctrl+C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Shutdown requested, waiting for in-flight requests");
+}
@@ -0,0 +1,217 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{
+    Router,
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+
+use crate::auth::session_store::DieselSessionStore;
+use crate::database::connection::AppState;
+use crate::error::{AppError, AppResult};
+
+/// Label used for requests that matched no route, so that random paths do not create new series.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Materials exported by the vein gauges, those yielding the most veins, so that the number of
+/// series stays bounded however many materials are recorded.
+const VEIN_METRIC_MATERIALS: usize = 50;
+
+/// Prometheus metrics of the server, exported by `/metrics`.
+///
+/// Request metrics are recorded as requests pass through [`track_metrics`]; the gauges are
+/// refreshed from the database on each scrape.
+#[derive(Clone)]
+pub struct Metrics {
+    inner: Arc<MetricsInner>,
+}
+
+struct MetricsInner {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    pool_connections: IntGaugeVec,
+    active_sessions: IntGauge,
+    veins: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("gt6_vein_manager".to_string()), None)
+            .expect("metric prefix is valid");
+
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled, by route"),
+            &["method", "route", "status"],
+        )
+        .expect("metric definition is valid");
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to answer HTTP requests, by route",
+            ),
+            &["method", "route"],
+        )
+        .expect("metric definition is valid");
+        let pool_connections = IntGaugeVec::new(
+            Opts::new(
+                "db_pool_connections",
+                "Database pool connections: max, open, idle and waiting requests",
+            ),
+            &["state"],
+        )
+        .expect("metric definition is valid");
+        let active_sessions = IntGauge::new("active_sessions", "Login sessions not yet expired")
+            .expect("metric definition is valid");
+        let veins = IntGaugeVec::new(
+            Opts::new(
+                "veins",
+                "Veins that are not revoked, by material (the materials with the most veins)",
+            ),
+            &["material", "state"],
+        )
+        .expect("metric definition is valid");
+
+        for collector in [
+            Box::new(requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(request_duration.clone()),
+            Box::new(pool_connections.clone()),
+            Box::new(active_sessions.clone()),
+            Box::new(veins.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("metric names are unique");
+        }
+
+        Self {
+            inner: Arc::new(MetricsInner {
+                registry,
+                requests,
+                request_duration,
+                pool_connections,
+                active_sessions,
+                veins,
+            }),
+        }
+    }
+
+    fn observe_request(&self, method: &str, route: &str, status: u16, seconds: f64) {
+        self.inner
+            .requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.inner
+            .request_duration
+            .with_label_values(&[method, route])
+            .observe(seconds);
+    }
+
+    /// Reads the gauges from the database and renders every metric in the text format.
+    async fn gather(&self, state: &AppState) -> AppResult<String> {
+        let inner = &self.inner;
+
+        let pool = state.diesel_pool.status();
+        for (label, value) in [
+            ("max", pool.max_size),
+            ("open", pool.size),
+            ("idle", pool.available),
+            ("waiting", pool.waiting),
+        ] {
+            inner
+                .pool_connections
+                .with_label_values(&[label])
+                .set(value as i64);
+        }
+
+        let sessions = DieselSessionStore::new(state.diesel_pool.clone())
+            .count_active_sessions()
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to count sessions: {}", e)))?;
+        inner.active_sessions.set(sessions);
+
+        // 数えられなくなった鉱石の系列が残らないよう、毎回作り直す
+        let counts = state
+            .vein_repository
+            .count_veins(VEIN_METRIC_MATERIALS)
+            .await?;
+        inner.veins.reset();
+        for count in counts {
+            for (label, value) in [
+                ("total", count.total),
+                ("confirmed", count.confirmed),
+                ("depleted", count.depleted),
+            ] {
+                inner
+                    .veins
+                    .with_label_values(&[count.material.as_str(), label])
+                    .set(value as i64);
+            }
+        }
+
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&inner.registry.gather(), &mut buffer)
+            .map_err(|e| AppError::Internal(format!("Failed to encode metrics: {}", e)))?;
+        String::from_utf8(buffer)
+            .map_err(|e| AppError::Internal(format!("Failed to encode metrics: {}", e)))
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// リクエスト数と処理時間をルートごとに記録するミドルウェア
+// ラベルには実際のパスではなくルートの定義（/veins/{id}/confirm など）を使う
+pub async fn track_metrics(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    state.metrics.observe_request(
+        &method,
+        &route,
+        response.status().as_u16(),
+        started.elapsed().as_secs_f64(),
+    );
+    response
+}
+
+pub async fn metrics_handler(State(state): State<AppState>) -> AppResult<Response> {
+    let body = state.metrics.gather(&state).await?;
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            TextEncoder::new().format_type().to_string(),
+        )],
+        body,
+    )
+        .into_response())
+}
+
+/// Router serving only `/metrics`, without login, for `metrics_address`.
+pub fn create_metrics_app(state: AppState) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(state)
+}
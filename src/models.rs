@@ -1,4 +1,5 @@
 pub mod auth;
 pub mod forms;
+pub mod saved_search;
 pub mod sessions;
 pub mod vein;
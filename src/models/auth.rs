@@ -16,7 +16,7 @@ pub struct User {
     pub invited_by: Option<String>,
 }
 
-#[derive(Queryable, Selectable)]
+#[derive(Queryable, Selectable, Clone, Debug)]
 #[diesel(table_name = crate::schema::invitation)]
 #[diesel(check_for_backend(diesel::mysql::Mysql))]
 pub struct Invitation {
@@ -1,9 +1,166 @@
-use serde::Deserialize;
+use std::fmt::Display;
+use std::str::FromStr;
 
-#[derive(Debug, Deserialize)]
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::de::{DeserializeOwned, IntoDeserializer, value::Error as ValueError};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::ores::{OreRole, VeinOre, find_vein_definition, normalize_composition};
+
+/// Lowest and highest Y of blocks in a GT6 (Minecraft 1.7.10) world.
+pub const WORLD_MIN_Y: i32 = 0;
+pub const WORLD_MAX_Y: i32 = 255;
+
+/// Veins per page when `limit` is not given.
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+/// Largest accepted `limit`.
+pub const MAX_PAGE_SIZE: u32 = 200;
+
+/// Longest material name `vein_ore.material` can hold, in characters.
+pub const MAX_MATERIAL_LENGTH: usize = 64;
+/// Most ores that can be entered for one vein.
+pub const MAX_COMPOSITION_ENTRIES: usize = 16;
+
+/// Column the search results are ordered by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    Name,
+    /// Horizontal distance from (`from_x`, `from_z`)
+    Distance,
+    /// Registration order
+    #[default]
+    CreatedAt,
+    /// Confirmed, then unconfirmed, depleted and revoked veins
+    Status,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn reversed(self) -> Self {
+        match self {
+            SortOrder::Asc => SortOrder::Desc,
+            SortOrder::Desc => SortOrder::Asc,
+        }
+    }
+}
+
+/// Notes searched by the keywords of a search.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteScope {
+    /// Only the current note of each vein
+    #[default]
+    Latest,
+    /// Every note ever recorded for the vein
+    All,
+}
+
+// 空の値を読み飛ばす `empty_as_none` で受け取れるよう、クエリー文字列と同じ綴りで読む
+impl FromStr for SortKey {
+    type Err = ValueError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        parse_variant(value)
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = ValueError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        parse_variant(value)
+    }
+}
+
+impl FromStr for NoteScope {
+    type Err = ValueError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        parse_variant(value)
+    }
+}
+
+fn parse_variant<T: DeserializeOwned>(value: &str) -> Result<T, ValueError> {
+    T::deserialize(value.into_deserializer())
+}
+
+/// Part of a text shown in the search results, marked when it matches a keyword.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextSegment {
+    pub text: String,
+    pub matched: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct SearchQuery {
     pub name: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
     pub include_revoked: Option<bool>,
+    /// Material the vein must contain, in any role
+    pub material: Option<String>,
+    /// Keywords separated by spaces, each of which must appear in the name or the notes.
+    pub text: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub note_scope: Option<NoteScope>,
+    /// Latest status to match; missing or empty matches either value.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub confirmed: Option<bool>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub depleted: Option<bool>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub bedrock: Option<bool>,
+    /// First and last registration day to match, both included.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub created_from: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub created_to: Option<NaiveDate>,
+    /// Heights the player can reach. Veins overlapping them match; veins of unknown height don't.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub y_from: Option<i32>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub y_to: Option<i32>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub sort: Option<SortKey>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub order: Option<SortOrder>,
+    /// Point the distance sort is measured from. Missing coordinates count as 0.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub from_x: Option<i32>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub from_z: Option<i32>,
+    /// 1-based page number
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub page: Option<u32>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub limit: Option<u32>,
+    /// Only veins pinned by this user. Set by the server, never read from the request.
+    #[serde(skip)]
+    pub favorites_of: Option<String>,
+}
+
+/// Reads an empty form field (`from_x=`) as a missing value instead of failing to parse it.
+fn empty_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        _ => Ok(None),
+    }
 }
 
 impl SearchQuery {
@@ -20,32 +177,255 @@ impl SearchQuery {
             .map(|s| s.as_str())
     }
 
+    /// The "where can I get it" search: veins yielding the material that are not depleted,
+    /// nearest to the point first.
+    pub fn yielding(material: &str, from_x: Option<i32>, from_z: Option<i32>) -> Self {
+        Self {
+            material: Some(material.to_string()),
+            depleted: Some(false),
+            sort: Some(SortKey::Distance),
+            from_x,
+            from_z,
+            ..Default::default()
+        }
+    }
+
+    pub fn get_material_filter(&self) -> Option<&str> {
+        self.material
+            .as_deref()
+            .map(str::trim)
+            .filter(|material| !material.is_empty())
+    }
+
     pub fn should_include_revoked(&self) -> bool {
         self.include_revoked.unwrap_or(false)
     }
 
+    /// Lowercased keywords of the free-text search, without duplicates.
+    pub fn text_terms(&self) -> Vec<String> {
+        let mut terms: Vec<String> = Vec::new();
+        for term in self.text.iter().flat_map(|text| text.split_whitespace()) {
+            let term = term.to_lowercase();
+            if !terms.contains(&term) {
+                terms.push(term);
+            }
+        }
+        terms
+    }
+
+    pub fn note_scope(&self) -> NoteScope {
+        self.note_scope.unwrap_or_default()
+    }
+
+    /// Splits `text` so that the parts matching a keyword can be marked, ignoring case.
+    pub fn highlight(&self, text: &str) -> Vec<TextSegment> {
+        let terms = self.text_terms();
+        let mut segments: Vec<TextSegment> = Vec::new();
+        let mut push = |part: &str, matched: bool| match segments.last_mut() {
+            Some(last) if last.matched == matched => last.text.push_str(part),
+            _ => segments.push(TextSegment {
+                text: part.to_string(),
+                matched,
+            }),
+        };
+
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            // 一番長く一致するキーワードを強調する
+            let matched = terms
+                .iter()
+                .filter_map(|term| match_length(rest, term))
+                .max();
+            let length = matched.unwrap_or(c.len_utf8());
+            push(&rest[..length], matched.is_some());
+            rest = &rest[length..];
+        }
+        segments
+    }
+
+    /// Earliest registration time to match.
+    pub fn created_since(&self) -> Option<NaiveDateTime> {
+        self.created_from.map(|date| date.and_time(NaiveTime::MIN))
+    }
+
+    /// Registration time from which veins no longer match: the day after `created_to`.
+    pub fn created_until(&self) -> Option<NaiveDateTime> {
+        self.created_to
+            .and_then(|date| date.succ_opt())
+            .map(|date| date.and_time(NaiveTime::MIN))
+    }
+
+    pub fn sort_key(&self) -> SortKey {
+        self.sort.unwrap_or_default()
+    }
+
+    pub fn sort_order(&self) -> SortOrder {
+        self.order.unwrap_or_default()
+    }
+
+    /// Point the distance sort is measured from.
+    pub fn origin(&self) -> (i32, i32) {
+        (self.from_x.unwrap_or(0), self.from_z.unwrap_or(0))
+    }
+
+    pub fn page(&self) -> u32 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// Number of veins before the current page.
+    pub fn offset(&self) -> u64 {
+        u64::from(self.page() - 1) * u64::from(self.limit())
+    }
+
+    /// 検索条件をクエリ文字列に戻す（値は URL エンコードされる）
     pub fn get_all_query_string(&self) -> String {
-        let mut query = String::new();
+        let mut pairs: Vec<(&str, String)> = Vec::new();
         if let Some(name) = &self.name {
-            query.push_str(&format!("name={}&", name));
+            pairs.push(("name", name.clone()));
         }
         if let Some(include_revoked) = self.include_revoked {
-            query.push_str(&format!("include_revoked={}&", include_revoked));
+            pairs.push(("include_revoked", include_revoked.to_string()));
+        }
+        if let Some(material) = &self.material {
+            pairs.push(("material", material.clone()));
+        }
+        if let Some(text) = &self.text {
+            pairs.push(("text", text.clone()));
         }
-        query.trim_end_matches('&').to_string()
+        if let Some(note_scope) = self.note_scope {
+            pairs.push(("note_scope", enum_value(note_scope)));
+        }
+        for (key, value) in [
+            ("confirmed", self.confirmed),
+            ("depleted", self.depleted),
+            ("bedrock", self.bedrock),
+        ] {
+            if let Some(value) = value {
+                pairs.push((key, value.to_string()));
+            }
+        }
+        if let Some(created_from) = self.created_from {
+            pairs.push(("created_from", created_from.to_string()));
+        }
+        if let Some(created_to) = self.created_to {
+            pairs.push(("created_to", created_to.to_string()));
+        }
+        if let Some(y_from) = self.y_from {
+            pairs.push(("y_from", y_from.to_string()));
+        }
+        if let Some(y_to) = self.y_to {
+            pairs.push(("y_to", y_to.to_string()));
+        }
+        if let Some(sort) = self.sort {
+            pairs.push(("sort", enum_value(sort)));
+        }
+        if let Some(order) = self.order {
+            pairs.push(("order", enum_value(order)));
+        }
+        if let Some(from_x) = self.from_x {
+            pairs.push(("from_x", from_x.to_string()));
+        }
+        if let Some(from_z) = self.from_z {
+            pairs.push(("from_z", from_z.to_string()));
+        }
+        if let Some(page) = self.page {
+            pairs.push(("page", page.to_string()));
+        }
+        if let Some(limit) = self.limit {
+            pairs.push(("limit", limit.to_string()));
+        }
+        serde_urlencoded::to_string(&pairs).unwrap_or_default()
     }
+
+    /// The current search on another page.
+    pub fn page_query_string(&self, page: u32) -> String {
+        SearchQuery {
+            page: Some(page),
+            ..self.clone()
+        }
+        .get_all_query_string()
+    }
+
+    /// The current search ordered by `key`, from the first page.
+    /// Choosing the current key again reverses the order.
+    pub fn sort_query_string(&self, key: SortKey) -> String {
+        let order = if self.sort_key() == key {
+            self.sort_order().reversed()
+        } else {
+            SortOrder::Asc
+        };
+        SearchQuery {
+            sort: Some(key),
+            order: Some(order),
+            page: None,
+            ..self.clone()
+        }
+        .get_all_query_string()
+    }
+
+    /// Arrow shown next to the column the results are ordered by.
+    pub fn sort_indicator(&self, key: SortKey) -> &'static str {
+        if self.sort_key() != key {
+            return "";
+        }
+        match self.sort_order() {
+            SortOrder::Asc => "▲",
+            SortOrder::Desc => "▼",
+        }
+    }
+}
+
+/// Length in bytes of the start of `text` that equals the lowercased `term`, ignoring case.
+fn match_length(text: &str, term: &str) -> Option<usize> {
+    let mut expected = term.chars();
+    let mut pending = Vec::new();
+    for (index, c) in text.char_indices() {
+        pending.extend(c.to_lowercase());
+        for lowered in pending.drain(..) {
+            if expected.next() != Some(lowered) {
+                return None;
+            }
+        }
+        if expected.as_str().is_empty() {
+            return Some(index + c.len_utf8());
+        }
+    }
+    None
+}
+
+/// Value of a unit enum variant as it appears in the query string.
+fn enum_value(value: impl Serialize) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
 }
 
 #[derive(Debug, Deserialize)]
 pub struct AddVeinForm {
     pub name: String,
     pub x_coord: String,
-    pub y_coord: String,
+    /// Lowest and highest Y. Either may be left empty for a vein one block high.
+    #[serde(default)]
+    pub y_min: String,
+    #[serde(default)]
+    pub y_max: String,
     pub z_coord: String,
     pub notes: Option<String>,
     pub confirmed: Option<bool>,
     pub depleted: Option<bool>,
     pub bedrock: Option<bool>,
+    /// Materials of each layer, separated by commas
+    pub ore_primary: Option<String>,
+    pub ore_secondary: Option<String>,
+    pub ore_between: Option<String>,
+    pub ore_sporadic: Option<String>,
 }
 
 impl AddVeinForm {
@@ -53,11 +433,44 @@ impl AddVeinForm {
         self.x_coord.parse::<i32>()
     }
 
-    pub fn parse_y_coord(&self) -> Result<Option<i32>, std::num::ParseIntError> {
-        if self.y_coord.trim().is_empty() {
-            Ok(None)
-        } else {
-            self.y_coord.parse::<i32>().map(Some)
+    /// The Y range of the vein, or `None` when no height was entered.
+    pub fn parse_y_range(&self) -> Result<Option<(i32, i32)>, String> {
+        let parse = |value: &str, label: &str| -> Result<Option<i32>, String> {
+            let value = value.trim();
+            if value.is_empty() {
+                return Ok(None);
+            }
+            let y = value
+                .parse::<i32>()
+                .map_err(|_| format!("{}が正しい整数ではありません。", label))?;
+            if !(WORLD_MIN_Y..=WORLD_MAX_Y).contains(&y) {
+                return Err(format!(
+                    "{}は{}から{}の範囲で入力してください。",
+                    label, WORLD_MIN_Y, WORLD_MAX_Y
+                ));
+            }
+            Ok(Some(y))
+        };
+
+        match (
+            parse(&self.y_min, "Y座標（下端）")?,
+            parse(&self.y_max, "Y座標（上端）")?,
+        ) {
+            (Some(min), Some(max)) if min > max => {
+                Err("Y座標の下端が上端より高くなっています。".to_string())
+            }
+            (Some(min), Some(max)) => Ok(Some((min, max))),
+            (Some(y), None) | (None, Some(y)) => Ok(Some((y, y))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Y range as entered, for messages, e.g. `12〜40`.
+    pub fn format_y_range(&self) -> String {
+        match self.parse_y_range() {
+            Ok(Some((min, max))) if min == max => min.to_string(),
+            Ok(Some((min, max))) => format!("{}〜{}", min, max),
+            _ => "-".to_string(),
         }
     }
 
@@ -76,4 +489,50 @@ impl AddVeinForm {
     pub fn is_bedrock(&self) -> bool {
         self.bedrock.unwrap_or(false)
     }
+
+    /// The entered composition, or the known one of the vein type when nothing was entered.
+    ///
+    /// Fails with a message for the user when a material is too long to store or too many are entered.
+    pub fn composition(&self) -> Result<Vec<VeinOre>, String> {
+        let mut ores: Vec<VeinOre> = [
+            (OreRole::Primary, &self.ore_primary),
+            (OreRole::Secondary, &self.ore_secondary),
+            (OreRole::Between, &self.ore_between),
+            (OreRole::Sporadic, &self.ore_sporadic),
+        ]
+        .into_iter()
+        .flat_map(|(role, materials)| {
+            materials
+                .as_deref()
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|material| !material.is_empty())
+                .map(move |material| VeinOre::new(material, role))
+        })
+        .collect();
+
+        if ores.is_empty() {
+            return Ok(find_vein_definition(&self.name)
+                .map(|definition| definition.composition())
+                .unwrap_or_default());
+        }
+        if ores
+            .iter()
+            .any(|ore| ore.material.chars().count() > MAX_MATERIAL_LENGTH)
+        {
+            return Err(format!(
+                "鉱石名は{}文字以内で入力してください。",
+                MAX_MATERIAL_LENGTH
+            ));
+        }
+        normalize_composition(&mut ores);
+        if ores.len() > MAX_COMPOSITION_ENTRIES {
+            return Err(format!(
+                "鉱石は1つの鉱脈につき{}件まで入力できます。",
+                MAX_COMPOSITION_ENTRIES
+            ));
+        }
+        Ok(ores)
+    }
 }
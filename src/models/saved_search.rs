@@ -0,0 +1,15 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+/// A search saved by a user under a name, shown as a link on the home page.
+#[derive(Queryable, Selectable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = crate::schema::saved_search)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct SavedSearch {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    /// Parameters of `/search`, as built by `SearchQuery::get_all_query_string`
+    pub query_string: String,
+    pub created_at: Option<NaiveDateTime>,
+}
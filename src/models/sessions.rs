@@ -8,6 +8,11 @@ pub struct Session {
     pub id: String,
     pub data: String,
     pub expiry_date: NaiveDateTime,
+    /// The following are recorded once the user is logged in
+    pub user_id: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub last_seen_at: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable)]
@@ -17,3 +22,55 @@ pub struct NewSession {
     pub data: String,
     pub expiry_date: NaiveDateTime,
 }
+
+/// A login session of a user, as listed on the "my sessions" page.
+#[derive(Debug, Clone)]
+pub struct ActiveSession {
+    /// Names the session in forms. The id itself is the cookie value and is never shown.
+    pub handle: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub last_seen_at: Option<NaiveDateTime>,
+    pub expiry_date: NaiveDateTime,
+    /// The session of the request showing the list
+    pub current: bool,
+}
+
+impl ActiveSession {
+    pub fn new(session: Session, current_id: Option<&str>) -> Self {
+        Self {
+            handle: session_handle(&session.id),
+            current: current_id == Some(session.id.as_str()),
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            last_seen_at: session.last_seen_at,
+            expiry_date: session.expiry_date,
+        }
+    }
+
+    pub fn format_user_agent(&self) -> &str {
+        self.user_agent.as_deref().unwrap_or("不明")
+    }
+
+    pub fn format_ip_address(&self) -> &str {
+        self.ip_address.as_deref().unwrap_or("-")
+    }
+
+    pub fn format_last_seen_at(&self) -> String {
+        self.last_seen_at.map_or_else(
+            || "-".to_string(),
+            |dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        )
+    }
+}
+
+/// Stable, non-secret name of a session id: the start of its SHA-256 digest.
+pub fn session_handle(session_id: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(session_id.as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
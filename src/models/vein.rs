@@ -8,9 +8,10 @@ pub struct Vein {
     pub id: String,
     pub name: String,
     pub x_coord: i32,
-    pub y_coord: Option<i32>,
     pub z_coord: i32,
     pub created_at: Option<NaiveDateTime>,
+    pub y_min: Option<i32>,
+    pub y_max: Option<i32>,
 }
 
 #[derive(Queryable, Selectable)]
@@ -0,0 +1,236 @@
+//! Ore materials of veins and the large vein types of GregTech 6.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+
+/// Layer of a large vein an ore is generated in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OreRole {
+    Primary,
+    Secondary,
+    Between,
+    Sporadic,
+}
+
+impl OreRole {
+    pub const ALL: [OreRole; 4] = [
+        OreRole::Primary,
+        OreRole::Secondary,
+        OreRole::Between,
+        OreRole::Sporadic,
+    ];
+
+    /// Value stored in `vein_ore.role` and used in forms.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OreRole::Primary => "primary",
+            OreRole::Secondary => "secondary",
+            OreRole::Between => "between",
+            OreRole::Sporadic => "sporadic",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OreRole::Primary => "主鉱石",
+            OreRole::Secondary => "副鉱石",
+            OreRole::Between => "中間層",
+            OreRole::Sporadic => "散在",
+        }
+    }
+}
+
+impl fmt::Display for OreRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OreRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OreRole::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| format!("unknown ore role: {}", s))
+    }
+}
+
+/// One ore material of a vein.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VeinOre {
+    pub material: String,
+    pub role: OreRole,
+}
+
+impl VeinOre {
+    pub fn new(material: impl Into<String>, role: OreRole) -> Self {
+        Self {
+            material: material.into(),
+            role,
+        }
+    }
+}
+
+/// Sorts a composition by role, then material, and drops repeated entries.
+pub fn normalize_composition(ores: &mut Vec<VeinOre>) {
+    ores.sort_by(|a, b| (a.role, &a.material).cmp(&(b.role, &b.material)));
+    ores.dedup();
+}
+
+/// Ores of a large vein type as generated by the game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VeinDefinition {
+    /// Name of the vein type, as entered for veins
+    pub name: String,
+    pub primary: String,
+    pub secondary: String,
+    pub between: String,
+    pub sporadic: String,
+}
+
+impl VeinDefinition {
+    pub fn composition(&self) -> Vec<VeinOre> {
+        let mut ores = vec![
+            VeinOre::new(&self.primary, OreRole::Primary),
+            VeinOre::new(&self.secondary, OreRole::Secondary),
+            VeinOre::new(&self.between, OreRole::Between),
+            VeinOre::new(&self.sporadic, OreRole::Sporadic),
+        ];
+        normalize_composition(&mut ores);
+        ores
+    }
+
+    /// Whether the vein type yields the material in any layer, ignoring case.
+    pub fn yields(&self, material: &str) -> bool {
+        [
+            &self.primary,
+            &self.secondary,
+            &self.between,
+            &self.sporadic,
+        ]
+        .iter()
+        .any(|ore| ore.eq_ignore_ascii_case(material.trim()))
+    }
+}
+
+/// Reads vein definitions from tab separated lines of a name and the materials of the four layers.
+///
+/// Empty lines and lines starting with `#` are skipped.
+pub fn parse_vein_definitions(tsv: &str) -> Result<Vec<VeinDefinition>, String> {
+    let mut definitions: Vec<VeinDefinition> = Vec::new();
+    for (index, line) in tsv.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+        let [name, primary, secondary, between, sporadic] = fields[..] else {
+            return Err(format!(
+                "line {}: expected 5 tab separated fields, found {}",
+                index + 1,
+                fields.len()
+            ));
+        };
+        if fields.iter().any(|field| field.is_empty()) {
+            return Err(format!("line {}: empty field", index + 1));
+        }
+        // 名前が重複すると、構成の補完先が曖昧になる
+        if definitions
+            .iter()
+            .any(|definition| definition.name.eq_ignore_ascii_case(name))
+        {
+            return Err(format!("line {}: duplicate vein type {}", index + 1, name));
+        }
+        definitions.push(VeinDefinition {
+            name: name.to_string(),
+            primary: primary.to_string(),
+            secondary: secondary.to_string(),
+            between: between.to_string(),
+            sporadic: sporadic.to_string(),
+        });
+    }
+    Ok(definitions)
+}
+
+/// `data/large_veins.tsv`, generated by `extract_ores.sh` from the `WorldgenOresLarge` entries of
+/// GT6's `Loader_Worldgen.java`.
+pub const LARGE_VEINS_TSV: &str = include_str!("../data/large_veins.tsv");
+
+/// Large vein types of GT6, read from [`LARGE_VEINS_TSV`].
+pub fn large_veins() -> &'static [VeinDefinition] {
+    static LARGE_VEINS: LazyLock<Vec<VeinDefinition>> = LazyLock::new(|| {
+        parse_vein_definitions(LARGE_VEINS_TSV).expect("data/large_veins.tsv is well-formed")
+    });
+    &LARGE_VEINS
+}
+
+/// The definition of a vein type by name, ignoring case and surrounding spaces.
+pub fn find_vein_definition(name: &str) -> Option<&'static VeinDefinition> {
+    let name = name.trim();
+    large_veins()
+        .iter()
+        .find(|definition| definition.name.eq_ignore_ascii_case(name))
+}
+
+/// Names of the vein types that yield the material.
+pub fn vein_types_yielding(material: &str) -> Vec<&'static str> {
+    large_veins()
+        .iter()
+        .filter(|definition| definition.yields(material))
+        .map(|definition| definition.name.as_str())
+        .collect()
+}
+
+/// One material of the material index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaterialEntry {
+    pub material: String,
+    /// Vein types yielding the material, empty for materials only recorded on veins
+    pub vein_types: Vec<String>,
+}
+
+/// Every material of the vein definitions and of the recorded compositions, in name order.
+///
+/// Materials differing only in case are listed once, with the spelling of the definitions,
+/// or the first spelling recorded.
+pub fn material_index(
+    definitions: &[VeinDefinition],
+    recorded: impl IntoIterator<Item = String>,
+) -> Vec<MaterialEntry> {
+    let mut entries: Vec<MaterialEntry> = Vec::new();
+    let known = definitions
+        .iter()
+        .flat_map(|definition| {
+            [
+                &definition.primary,
+                &definition.secondary,
+                &definition.between,
+                &definition.sporadic,
+            ]
+        })
+        .cloned();
+    for material in known.chain(recorded) {
+        if entries
+            .iter()
+            .any(|entry| entry.material.eq_ignore_ascii_case(&material))
+        {
+            continue;
+        }
+        let vein_types = definitions
+            .iter()
+            .filter(|definition| definition.yields(&material))
+            .map(|definition| definition.name.clone())
+            .collect();
+        entries.push(MaterialEntry {
+            material,
+            vein_types,
+        });
+    }
+    entries.sort_by_key(|entry| entry.material.to_lowercase());
+    entries
+}
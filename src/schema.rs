@@ -1,5 +1,17 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    favorite_vein (id) {
+        #[max_length = 36]
+        id -> Varchar,
+        #[max_length = 36]
+        user_id -> Varchar,
+        #[max_length = 36]
+        vein_id -> Varchar,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     invitation (id) {
         #[max_length = 36]
@@ -18,12 +30,33 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    saved_search (id) {
+        #[max_length = 36]
+        id -> Varchar,
+        #[max_length = 36]
+        user_id -> Varchar,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 2048]
+        query_string -> Varchar,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     sessions (id) {
         #[max_length = 255]
         id -> Varchar,
         data -> Longtext,
         expiry_date -> Timestamp,
+        #[max_length = 36]
+        user_id -> Nullable<Varchar>,
+        #[max_length = 512]
+        user_agent -> Nullable<Varchar>,
+        #[max_length = 45]
+        ip_address -> Nullable<Varchar>,
+        last_seen_at -> Nullable<Timestamp>,
     }
 }
 
@@ -52,9 +85,10 @@ diesel::table! {
         #[max_length = 255]
         name -> Varchar,
         x_coord -> Integer,
-        y_coord -> Nullable<Integer>,
         z_coord -> Integer,
         created_at -> Nullable<Timestamp>,
+        y_min -> Nullable<Integer>,
+        y_max -> Nullable<Integer>,
     }
 }
 
@@ -103,6 +137,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    vein_ore (id) {
+        #[max_length = 36]
+        id -> Varchar,
+        #[max_length = 36]
+        vein_id -> Varchar,
+        #[max_length = 64]
+        material -> Varchar,
+        #[max_length = 16]
+        role -> Varchar,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     vein_revocation (id) {
         #[max_length = 36]
@@ -114,15 +162,21 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(favorite_vein -> user (user_id));
+diesel::joinable!(favorite_vein -> vein (vein_id));
 diesel::joinable!(invitation -> user (used_by));
+diesel::joinable!(saved_search -> user (user_id));
 diesel::joinable!(vein_confirmation -> vein (vein_id));
 diesel::joinable!(vein_depletion -> vein (vein_id));
 diesel::joinable!(vein_is_bedrock -> vein (vein_id));
 diesel::joinable!(vein_note -> vein (vein_id));
+diesel::joinable!(vein_ore -> vein (vein_id));
 diesel::joinable!(vein_revocation -> vein (vein_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    favorite_vein,
     invitation,
+    saved_search,
     sessions,
     user,
     vein,
@@ -130,5 +184,6 @@ diesel::allow_tables_to_appear_in_same_query!(
     vein_depletion,
     vein_is_bedrock,
     vein_note,
+    vein_ore,
     vein_revocation,
 );
@@ -0,0 +1,204 @@
+use askama::Template;
+use axum::response::Html;
+use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
+
+use crate::auth::csrf::csrf_token;
+use crate::auth::throttle::LockoutEntry;
+use crate::database::queries::VeinWithStatus;
+use crate::error::AppError;
+use crate::models::auth::User;
+use crate::models::forms::{SearchQuery, SortKey};
+use crate::models::saved_search::SavedSearch;
+use crate::models::sessions::ActiveSession;
+use crate::ores::{MaterialEntry, VeinDefinition};
+
+/// Session key under which pending flash messages are kept until the next page render.
+const FLASH_SESSION_KEY: &str = "flash_messages";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FlashLevel {
+    Success,
+    Error,
+}
+
+impl FlashLevel {
+    /// CSS class used by `styles.css` for the message box.
+    pub fn css_class(&self) -> &'static str {
+        match self {
+            FlashLevel::Success => "success",
+            FlashLevel::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Flash {
+    pub level: FlashLevel,
+    pub message: String,
+}
+
+/// Queues a message to be shown once on the next rendered page.
+pub async fn push_flash(
+    session: &Session,
+    level: FlashLevel,
+    message: impl Into<String>,
+) -> Result<(), AppError> {
+    let mut flashes: Vec<Flash> = session.get(FLASH_SESSION_KEY).await?.unwrap_or_default();
+    flashes.push(Flash {
+        level,
+        message: message.into(),
+    });
+    session.insert(FLASH_SESSION_KEY, flashes).await?;
+    Ok(())
+}
+
+/// The part of the logged-in user shown in the navigation.
+pub struct CurrentUser {
+    pub username: String,
+    pub is_admin: bool,
+}
+
+/// Data required by `base.html`, shared by every page.
+pub struct Layout {
+    pub user: Option<CurrentUser>,
+    pub csrf_token: String,
+    pub flashes: Vec<Flash>,
+}
+
+impl Layout {
+    /// Collects the CSRF token and consumes pending flash messages from the session.
+    pub async fn load(session: &Session, user: Option<&User>) -> Result<Self, AppError> {
+        let csrf_token = csrf_token(session).await?;
+        let flashes = session
+            .remove::<Vec<Flash>>(FLASH_SESSION_KEY)
+            .await?
+            .unwrap_or_default();
+
+        Ok(Self {
+            user: user.map(|user| CurrentUser {
+                username: user.username.clone(),
+                is_admin: user.is_admin.unwrap_or(false),
+            }),
+            csrf_token,
+            flashes,
+        })
+    }
+}
+
+/// Renders a template into an HTML response.
+pub fn render(template: &impl Template) -> Result<Html<String>, AppError> {
+    Ok(Html(template.render()?))
+}
+
+#[derive(Template)]
+#[template(path = "index.html")]
+pub struct IndexTemplate {
+    pub layout: Layout,
+    pub saved_searches: Vec<SavedSearch>,
+    /// Veins pinned by the user, including revoked ones
+    pub favorites: Vec<VeinWithStatus>,
+    /// Known vein types, offered by the add form to fill in the composition
+    pub vein_definitions: &'static [VeinDefinition],
+}
+
+#[derive(Template)]
+#[template(path = "materials.html")]
+pub struct MaterialsTemplate {
+    pub layout: Layout,
+    pub materials: Vec<MaterialEntry>,
+    /// Point the searches linked from the index are measured from
+    pub from_x: Option<i32>,
+    pub from_z: Option<i32>,
+}
+
+impl MaterialsTemplate {
+    /// Query string of the "where can I get it" search for the material.
+    pub fn yielding_query_string(&self, material: &str) -> String {
+        SearchQuery::yielding(material, self.from_x, self.from_z).get_all_query_string()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "search_results.html")]
+pub struct SearchResultsTemplate {
+    pub layout: Layout,
+    pub search_info: String,
+    pub query: SearchQuery,
+    /// Veins on the current page
+    pub veins: Vec<VeinWithStatus>,
+    /// Matching veins on all pages
+    pub total: usize,
+    /// Ids of the veins pinned by the user
+    pub favorite_ids: Vec<String>,
+    /// Current search parameters, posted back with each action so the redirect keeps them.
+    pub query_state: String,
+}
+
+impl SearchResultsTemplate {
+    pub fn page_count(&self) -> u32 {
+        let limit = self.query.limit() as usize;
+        u32::try_from(self.total.div_ceil(limit).max(1)).unwrap_or(u32::MAX)
+    }
+
+    pub fn has_previous_page(&self) -> bool {
+        self.query.page() > 1
+    }
+
+    pub fn has_next_page(&self) -> bool {
+        self.query.page() < self.page_count()
+    }
+
+    pub fn is_favorite(&self, vein_id: &str) -> bool {
+        self.favorite_ids.iter().any(|id| id == vein_id)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "login.html")]
+pub struct LoginTemplate {
+    pub layout: Layout,
+}
+
+#[derive(Template)]
+#[template(path = "register.html")]
+pub struct RegisterTemplate {
+    pub layout: Layout,
+    pub token: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "invitation_form.html")]
+pub struct InvitationFormTemplate {
+    pub layout: Layout,
+}
+
+#[derive(Template)]
+#[template(path = "invitation_issued.html")]
+pub struct InvitationIssuedTemplate {
+    pub layout: Layout,
+    pub invitation_url: String,
+    pub valid_hours: u32,
+}
+
+#[derive(Template)]
+#[template(path = "lockouts.html")]
+pub struct LockoutsTemplate {
+    pub layout: Layout,
+    pub lockouts: Vec<LockoutEntry>,
+}
+
+#[derive(Template)]
+#[template(path = "sessions.html")]
+pub struct SessionsTemplate {
+    pub layout: Layout,
+    pub sessions: Vec<ActiveSession>,
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+pub struct ErrorTemplate {
+    pub layout: Layout,
+    pub heading: String,
+    pub message: String,
+}
@@ -0,0 +1,850 @@
+//! End-to-end flows through the router returned by `create_app`.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{PASSWORD, TestApp, unique};
+use gt6_vein_manager::auth::repository::{AuthRepository, SqlAuthRepository};
+
+/// Picks the id of the first vein out of the action buttons on the search page.
+fn first_vein_id(html: &str) -> String {
+    let marker = "/api/veins/";
+    let start = html.find(marker).expect("the page lists a vein") + marker.len();
+    let end = start + html[start..].find('/').unwrap();
+    html[start..end].to_string()
+}
+
+async fn add_vein(app: &mut TestApp, name: &str, fields: &[(&str, &str)]) {
+    let mut form = vec![
+        ("name", name),
+        ("x_coord", "120"),
+        ("y_min", "32"),
+        ("y_max", "48"),
+        ("z_coord", "-80"),
+        ("notes", "near spawn"),
+    ];
+    // 指定された項目は既定値を置き換える
+    form.retain(|(key, _)| fields.iter().all(|(field, _)| field != key));
+    form.extend_from_slice(fields);
+    app.post_form("/api/veins/add", &form)
+        .await
+        .assert_redirect("/");
+}
+
+async fn search(app: &mut TestApp, query: &str) -> String {
+    let response = app.get(&format!("/search?{}", query)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    response.body
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn register_with_invitation_then_login_and_logout() {
+    let mut app = TestApp::spawn().await;
+    let token = app.system_invitation().await;
+
+    let page = app.get(&format!("/auth/register?token={}", token)).await;
+    assert_eq!(page.status, StatusCode::OK);
+    assert!(page.body.contains(&token));
+
+    let username = unique("alice");
+    app.post_form(
+        "/auth/register",
+        &[
+            ("username", &username),
+            ("email", "alice@example.com"),
+            ("password", PASSWORD),
+            ("token", &token),
+        ],
+    )
+    .await
+    .assert_redirect("/auth/login");
+
+    // 招待は一度しか使えない
+    let register_again = app
+        .post_form(
+            "/auth/register",
+            &[
+                ("username", &unique("mallory")),
+                ("password", PASSWORD),
+                ("token", &token),
+            ],
+        )
+        .await;
+    register_again.assert_redirect(&format!("/auth/register?token={}", token));
+    let page = app.get(&format!("/auth/register?token={}", token)).await;
+    assert!(page.body.contains("無効な招待トークンまたは期限切れです"));
+
+    // 未ログインでは保護されたページに入れない
+    app.get("/").await.assert_redirect("/auth/login");
+
+    app.login(&username, "wrong-password")
+        .await
+        .assert_redirect("/auth/login");
+    let page = app.get("/auth/login").await;
+    assert!(
+        page.body
+            .contains("ユーザー名またはパスワードが正しくありません")
+    );
+
+    app.login(&username, PASSWORD).await.assert_redirect("/");
+    let home = app.get("/").await;
+    assert_eq!(home.status, StatusCode::OK);
+    assert!(home.body.contains(&username));
+
+    // システム招待で登録したユーザーは管理者になる
+    let me = app.get("/api/auth/me").await;
+    assert_eq!(me.status, StatusCode::OK);
+    let me: serde_json::Value = serde_json::from_str(&me.body).unwrap();
+    assert_eq!(me["username"], username.as_str());
+    assert_eq!(me["email"], "alice@example.com");
+    assert_eq!(me["is_admin"], true);
+
+    app.post_form("/auth/logout", &[])
+        .await
+        .assert_redirect("/auth/login");
+    app.get("/").await.assert_redirect("/auth/login");
+    assert_eq!(app.get("/api/auth/me").await.status, StatusCode::SEE_OTHER);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn registration_rejects_unknown_invitation() {
+    let mut app = TestApp::spawn().await;
+    let username = unique("bob");
+
+    app.post_form(
+        "/auth/register",
+        &[
+            ("username", &username),
+            ("password", PASSWORD),
+            ("token", "not-a-token"),
+        ],
+    )
+    .await
+    .assert_redirect("/auth/register?token=not-a-token");
+
+    app.login(&username, PASSWORD)
+        .await
+        .assert_redirect("/auth/login");
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn invitations_record_the_issuing_admin() {
+    let mut app = TestApp::spawn().await;
+    let username = app.login_as_new_user().await;
+
+    let response = app
+        .post_form("/auth/issue-invitation", &[("email", "guest@example.com")])
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    let start = response.body.find("token=").unwrap() + "token=".len();
+    let end = start + response.body[start..].find('"').unwrap();
+    let token = &response.body[start..end];
+
+    let repository = SqlAuthRepository::new(app.pool.clone());
+    let admin = repository
+        .get_user_by_username(&username)
+        .await
+        .unwrap()
+        .unwrap();
+    let invitation = repository
+        .get_invitation_by_token(token)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(invitation.email.as_deref(), Some("guest@example.com"));
+    assert_eq!(invitation.invited_by, Some(admin.id));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn state_changing_requests_require_csrf_token() {
+    let mut app = TestApp::spawn().await;
+    app.login_as_new_user().await;
+
+    let response = app
+        .post_raw(
+            "/api/veins/add",
+            &[
+                ("name", &unique("Forged")),
+                ("x_coord", "0"),
+                ("y_min", ""),
+                ("z_coord", "0"),
+            ],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn added_vein_appears_in_search() {
+    let mut app = TestApp::spawn().await;
+    app.login_as_new_user().await;
+    let name = unique("Magnetite");
+
+    add_vein(&mut app, &name, &[("bedrock", "true")]).await;
+    let home = app.get("/").await;
+    assert!(home.body.contains("が正常に追加されました"));
+
+    let html = search(&mut app, &format!("name={}", name)).await;
+    assert!(html.contains("1 件の鉱脈が見つかりました"));
+    assert!(html.contains(&name));
+    assert!(html.contains("near spawn"));
+    assert!(html.contains("<td>120</td>"));
+    assert!(html.contains("<td>-80</td>"));
+
+    // 座標が数値でなければ登録されない
+    let invalid = unique("Invalid");
+    app.post_form(
+        "/api/veins/add",
+        &[
+            ("name", &invalid),
+            ("x_coord", "east"),
+            ("y_min", ""),
+            ("z_coord", "0"),
+        ],
+    )
+    .await
+    .assert_redirect("/");
+    let html = search(&mut app, &format!("name={}", invalid)).await;
+    assert!(html.contains("検索条件に一致する鉱脈が見つかりませんでした"));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn y_range_is_validated_and_filtered() {
+    let mut app = TestApp::spawn().await;
+    app.login_as_new_user().await;
+    let batch = unique("heights");
+
+    add_vein(&mut app, &format!("{}_range", batch), &[]).await;
+    // 片方だけ入力した場合は 1 ブロックの高さとして扱う
+    add_vein(
+        &mut app,
+        &format!("{}_single", batch),
+        &[("y_min", ""), ("y_max", "20")],
+    )
+    .await;
+    app.get("/").await;
+
+    for (y_min, y_max, message) in [
+        ("50", "10", "Y座標の下端が上端より高くなっています。"),
+        (
+            "-1",
+            "",
+            "Y座標（下端）は0から255の範囲で入力してください。",
+        ),
+        ("", "high", "Y座標（上端）が正しい整数ではありません。"),
+    ] {
+        add_vein(
+            &mut app,
+            &format!("{}_invalid", batch),
+            &[("y_min", y_min), ("y_max", y_max)],
+        )
+        .await;
+        assert!(app.get("/").await.body.contains(message));
+    }
+
+    let html = search(&mut app, &format!("name={}", batch)).await;
+    assert!(html.contains("2 件の鉱脈が見つかりました"));
+    assert!(html.contains("<td>32〜48</td>"));
+    assert!(html.contains("<td>20</td>"));
+
+    let html = search(&mut app, &format!("name={}&y_from=0&y_to=30", batch)).await;
+    assert!(html.contains("1 件の鉱脈が見つかりました"));
+    assert!(html.contains(&format!("{}_single", batch)));
+    assert!(html.contains("到達できる高さ: Y 0 〜 30"));
+    let html = search(&mut app, &format!("name={}&y_from=45", batch)).await;
+    assert!(html.contains("1 件の鉱脈が見つかりました"));
+    assert!(html.contains(&format!("{}_range", batch)));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn status_toggles_round_trip_through_search() {
+    let mut app = TestApp::spawn().await;
+    app.login_as_new_user().await;
+    let name = unique("Cassiterite");
+    add_vein(&mut app, &name, &[]).await;
+
+    let query = format!("name={}", name);
+    let id = first_vein_id(&search(&mut app, &query).await);
+    let action =
+        |target: &str, operation: &str| format!("/api/veins/{}/{}/{}", id, target, operation);
+
+    app.post_form(&action("confirmation", "set"), &[("query_state", &query)])
+        .await
+        .assert_redirect(&format!("/search?{}", query));
+    let html = search(&mut app, &query).await;
+    assert!(html.contains("視認解除"));
+    assert!(!html.contains("視認済みにする"));
+
+    app.post_form(&action("depletion", "set"), &[("query_state", &query)])
+        .await
+        .assert_redirect(&format!("/search?{}", query));
+    assert!(search(&mut app, &query).await.contains("枯渇解除"));
+
+    app.post_form(
+        &action("confirmation", "revoke"),
+        &[("query_state", &query)],
+    )
+    .await
+    .assert_redirect(&format!("/search?{}", query));
+    assert!(search(&mut app, &query).await.contains("視認済みにする"));
+
+    // 取り下げた鉱脈は既定では検索結果に出ない
+    app.post_form(&action("revocation", "set"), &[])
+        .await
+        .assert_redirect("/search");
+    assert!(
+        search(&mut app, &query)
+            .await
+            .contains("検索条件に一致する鉱脈が見つかりませんでした")
+    );
+    let html = search(&mut app, &format!("{}&include_revoked=true", query)).await;
+    assert!(html.contains("revoked-vein"));
+
+    app.post_form(&action("revocation", "revoke"), &[])
+        .await
+        .assert_redirect("/search");
+    assert!(search(&mut app, &query).await.contains(&name));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn search_filters_by_name_and_revocation() {
+    let mut app = TestApp::spawn().await;
+    app.login_as_new_user().await;
+    let batch = unique("batch");
+    let iron = format!("{}_iron", batch);
+    let copper = format!("{}_copper", batch);
+    add_vein(&mut app, &iron, &[]).await;
+    add_vein(&mut app, &copper, &[("confirmed", "true")]).await;
+
+    let html = search(&mut app, &format!("name={}", batch)).await;
+    assert!(html.contains("2 件の鉱脈が見つかりました"));
+
+    // 部分一致かつ大文字小文字を区別しない
+    let html = search(&mut app, &format!("name={}_IRON", batch.to_uppercase())).await;
+    assert!(html.contains("1 件の鉱脈が見つかりました"));
+    assert!(html.contains(&iron));
+    assert!(!html.contains(&copper));
+
+    let copper_id = first_vein_id(&search(&mut app, &format!("name={}", copper)).await);
+    app.post_form(&format!("/api/veins/{}/revocation/set", copper_id), &[])
+        .await
+        .assert_redirect("/search");
+
+    let html = search(&mut app, &format!("name={}", batch)).await;
+    assert!(html.contains("1 件の鉱脈が見つかりました"));
+    assert!(!html.contains(&copper));
+    let html = search(&mut app, &format!("name={}&include_revoked=true", batch)).await;
+    assert!(html.contains("2 件の鉱脈が見つかりました"));
+
+    // 検索条件はクエリ文字列として操作ボタンに引き継がれる
+    assert!(html.contains(&format!(
+        r#"name="query_state" value="name={}&#38;include_revoked=true""#,
+        batch
+    )));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn search_filters_by_status_flags() {
+    let mut app = TestApp::spawn().await;
+    app.login_as_new_user().await;
+    let batch = unique("flags");
+    let bedrock = format!("{}_bedrock", batch);
+    let depleted = format!("{}_depleted", batch);
+    add_vein(
+        &mut app,
+        &bedrock,
+        &[("confirmed", "true"), ("bedrock", "true")],
+    )
+    .await;
+    add_vein(
+        &mut app,
+        &depleted,
+        &[
+            ("confirmed", "true"),
+            ("depleted", "true"),
+            ("bedrock", "true"),
+        ],
+    )
+    .await;
+    app.get("/").await;
+
+    // 検索フォームの「指定なし」は空の値として送られる
+    let html = search(
+        &mut app,
+        &format!(
+            "name={}&confirmed=true&depleted=false&bedrock=true&created_from=&created_to=",
+            batch
+        ),
+    )
+    .await;
+    assert!(html.contains("1 件の鉱脈が見つかりました"));
+    assert!(html.contains(&bedrock));
+    assert!(!html.contains(&depleted));
+    assert!(html.contains("視認済み: はい, 枯渇済み: いいえ, 岩盤鉱脈: はい"));
+
+    let html = search(&mut app, &format!("name={}&depleted=&bedrock=true", batch)).await;
+    assert!(html.contains("2 件の鉱脈が見つかりました"));
+
+    let response = app.get("/search?created_from=yesterday").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn keywords_match_names_and_notes() {
+    let mut app = TestApp::spawn().await;
+    app.login_as_new_user().await;
+    let batch = unique("words");
+    let village = format!("{}_village", batch);
+    app.post_form(
+        "/api/veins/add",
+        &[
+            ("name", village.as_str()),
+            ("x_coord", "0"),
+            ("y_min", ""),
+            ("z_coord", "0"),
+            ("notes", "Under the village, needs silk touch"),
+        ],
+    )
+    .await
+    .assert_redirect("/");
+    add_vein(&mut app, &format!("{}_other", batch), &[]).await;
+    app.get("/").await;
+
+    let html = search(&mut app, &format!("text={}+SILK&note_scope=latest", batch)).await;
+    assert!(html.contains("1 件の鉱脈が見つかりました"));
+    assert!(html.contains("needs <mark>silk</mark> touch"));
+    assert!(html.contains(&format!("<mark>{}</mark>_village", batch)));
+    assert!(html.contains("キーワード:"));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn composition_is_searchable_by_material() {
+    let mut app = TestApp::spawn().await;
+    app.login_as_new_user().await;
+    let zinc = unique("Zinc");
+
+    // 構成を入力しなければ記録されず、鉱石の検索にも一致しない
+    add_vein(&mut app, "Cassiterite", &[]).await;
+    app.post_form(
+        "/api/veins/add",
+        &[
+            ("name", "Unknown vein"),
+            ("x_coord", "5"),
+            ("y_min", ""),
+            ("z_coord", "5"),
+            ("ore_primary", &zinc),
+            ("ore_sporadic", "Tin, Sphalerite"),
+        ],
+    )
+    .await
+    .assert_redirect("/");
+    app.get("/").await;
+
+    let html = search(&mut app, "material=tin&sort=name").await;
+    assert!(html.contains("1 件の鉱脈が見つかりました"));
+    assert!(html.contains("鉱石: tin"));
+    assert!(html.contains(&format!("{} (主鉱石), Sphalerite (散在), Tin (散在)", zinc)));
+    assert!(!html.contains("<strong>Cassiterite</strong>"));
+
+    let html = search(&mut app, &format!("material={}", zinc)).await;
+    assert!(html.contains("1 件の鉱脈が見つかりました"));
+    assert!(html.contains("Unknown vein"));
+
+    // 保存できない構成は鉱脈ごと追加しない
+    let rejected = unique("Rejected");
+    let long_material = "a".repeat(65);
+    let many_materials = (0..17)
+        .map(|i| format!("Ore{}", i))
+        .collect::<Vec<_>>()
+        .join(",");
+    for (materials, message) in [
+        (
+            long_material.as_str(),
+            "鉱石名は64文字以内で入力してください。",
+        ),
+        (
+            many_materials.as_str(),
+            "鉱石は1つの鉱脈につき16件まで入力できます。",
+        ),
+    ] {
+        add_vein(&mut app, &rejected, &[("ore_primary", materials)]).await;
+        assert!(app.get("/").await.body.contains(message));
+    }
+    let html = search(&mut app, &format!("name={}", rejected)).await;
+    assert!(html.contains("検索条件に一致する鉱脈が見つかりませんでした"));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn material_index_finds_nearest_veins_yielding_a_material() {
+    let mut app = TestApp::spawn().await;
+    app.login_as_new_user().await;
+    let batch = unique("where");
+
+    for (name, x, fields) in [
+        ("far", "900", vec![("ore_primary", "Tin, Cassiterite")]),
+        ("near", "30", vec![("ore_between", "Cassiterite")]),
+        (
+            "used_up",
+            "10",
+            vec![("ore_primary", "Cassiterite"), ("depleted", "true")],
+        ),
+        ("other", "20", vec![("ore_primary", "Tin")]),
+    ] {
+        let name = format!("{}_{}", batch, name);
+        let mut form = vec![
+            ("name", name.as_str()),
+            ("x_coord", x),
+            ("y_min", ""),
+            ("z_coord", "0"),
+        ];
+        form.extend(fields);
+        app.post_form("/api/veins/add", &form)
+            .await
+            .assert_redirect("/");
+    }
+    app.get("/").await;
+
+    let html = app.get("/materials?from_x=100&from_z=0").await.body;
+    assert!(html.contains("<strong>Cassiterite</strong>"));
+    assert!(html.contains("material=Cassiterite"));
+    assert!(html.contains("depleted=false"));
+    assert!(html.contains("sort=distance"));
+    assert!(html.contains("from_x=100"));
+
+    let html = search(
+        &mut app,
+        "material=Cassiterite&depleted=false&sort=distance&from_x=1000&from_z=0",
+    )
+    .await;
+    assert!(html.contains("2 件の鉱脈が見つかりました"));
+    assert!(!html.contains(&format!("{}_used_up", batch)));
+    assert!(!html.contains(&format!("{}_other", batch)));
+    let nearest = html.find(&format!("{}_far", batch)).unwrap();
+    let farther = html.find(&format!("{}_near", batch)).unwrap();
+    assert!(nearest < farther);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn search_results_are_paginated_and_sorted() {
+    let mut app = TestApp::spawn().await;
+    app.login_as_new_user().await;
+    let batch = unique("paged");
+    for suffix in ["c", "a", "b"] {
+        add_vein(&mut app, &format!("{}_{}", batch, suffix), &[]).await;
+    }
+    // 追加時のメッセージを読み終えておく
+    app.get("/").await;
+
+    let html = search(&mut app, &format!("name={}&sort=name&limit=2", batch)).await;
+    assert!(html.contains("3 件の鉱脈が見つかりました。（1 / 2 ページ）"));
+    assert!(html.find(&format!("{}_a", batch)) < html.find(&format!("{}_b", batch)));
+    assert!(!html.contains(&format!("{}_c", batch)));
+    // ページ送りのリンクは他の検索条件を引き継ぐ
+    let next = format!(
+        "/search?name={}&#38;sort=name&#38;page=2&#38;limit=2",
+        batch
+    );
+    assert!(html.contains(&next));
+
+    let html = search(
+        &mut app,
+        &format!("name={}&sort=name&limit=2&page=2", batch),
+    )
+    .await;
+    assert!(html.contains(&format!("{}_c", batch)));
+    assert!(!html.contains(&format!("{}_a", batch)));
+
+    let html = search(&mut app, &format!("name={}&sort=name&order=desc", batch)).await;
+    assert!(html.find(&format!("{}_c", batch)) < html.find(&format!("{}_a", batch)));
+
+    // 距離順では基準点からの距離を表示する
+    let html = search(
+        &mut app,
+        &format!("name={}&sort=distance&from_x=120&from_z=-80", batch),
+    )
+    .await;
+    assert!(html.contains("<th>距離</th>"));
+    assert!(html.contains("<td>0</td>"));
+
+    let response = app.get("/search?page=zero").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+/// Picks the id of the first saved search out of the delete buttons on the home page.
+fn first_saved_search_id(html: &str) -> String {
+    let marker = "/api/searches/";
+    let start = html.find(marker).expect("the page lists a saved search") + marker.len();
+    let end = start + html[start..].find('/').unwrap();
+    html[start..end].to_string()
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn saved_searches_and_favorites_appear_on_home_page() {
+    let mut app = TestApp::spawn().await;
+    app.login_as_new_user().await;
+    let vein = unique("pinned");
+    add_vein(&mut app, &vein, &[]).await;
+    app.get("/").await;
+
+    let query = format!("name={}&confirmed=false", vein);
+    let results = format!("/search?{}", query);
+    let id = first_vein_id(&search(&mut app, &query).await);
+
+    // ページ番号は保存しない
+    let with_page = format!("{}&page=2", query);
+    let save = [
+        ("name", "スポーン付近"),
+        ("query_state", with_page.as_str()),
+    ];
+    app.post_form("/api/searches/save", &save)
+        .await
+        .assert_redirect(&results);
+    app.post_form("/api/searches/save", &save)
+        .await
+        .assert_redirect(&results);
+    assert!(
+        search(&mut app, &query)
+            .await
+            .contains("「スポーン付近」という名前の検索は既に保存されています")
+    );
+
+    app.post_form(
+        &format!("/api/veins/{}/favorite/set", id),
+        &[("query_state", &query)],
+    )
+    .await
+    .assert_redirect(&results);
+    assert!(search(&mut app, &query).await.contains("★ お気に入り解除"));
+
+    let home = app.get("/").await.body;
+    assert!(home.contains(&format!(
+        r#"<a href="/search?name={}&#38;confirmed=false">スポーン付近</a>"#,
+        vein
+    )));
+    assert!(home.contains(&format!("<strong>{}</strong>", vein)));
+    let saved_id = first_saved_search_id(&home);
+
+    // 保存した検索とお気に入りは利用者ごと
+    let owner = app.swap_cookies(Default::default());
+    app.login_as_new_user().await;
+    let home = app.get("/").await.body;
+    assert!(!home.contains("スポーン付近"));
+    assert!(!home.contains(&vein));
+    let response = app
+        .post_form(&format!("/api/searches/{}/delete", saved_id), &[])
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    app.swap_cookies(owner);
+
+    app.post_form(&format!("/api/searches/{}/delete", saved_id), &[])
+        .await
+        .assert_redirect("/");
+    app.post_form(
+        &format!("/api/veins/{}/favorite/revoke", id),
+        &[("return_home", "true")],
+    )
+    .await
+    .assert_redirect("/");
+    let home = app.get("/").await.body;
+    assert!(home.contains("保存した検索はありません"));
+    assert!(home.contains("お気に入りの鉱脈はありません"));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn errors_are_rendered_as_page_or_json() {
+    let mut app = TestApp::spawn().await;
+    app.login_as_new_user().await;
+
+    let unknown = format!("/api/veins/{}/confirmation/set", uuid::Uuid::new_v4());
+    let response = app.post_form(&unknown, &[]).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert!(response.body.contains("<!DOCTYPE html>"));
+    assert!(response.body.contains("指定された鉱脈が見つかりません"));
+
+    let response = app.get_json("/static/missing.js").await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(body["status"], 404);
+    assert_eq!(body["error"], "ファイルが見つかりません");
+
+    // 読み取れない入力は axum の説明ではなく入力エラーとして返す
+    let response = app.get_json("/search?sort=height").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(
+        body["error"],
+        "検索条件を読み取れませんでした。入力内容を確認してください。"
+    );
+    let response = app.get("/search?page=first").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert!(response.body.contains("入力エラー"));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn metrics_are_exported_to_admins() {
+    let mut app = TestApp::spawn().await;
+    assert_eq!(app.get("/metrics").await.status, StatusCode::UNAUTHORIZED);
+
+    app.login_as_new_user().await;
+    let material = unique("Cassiterite");
+    add_vein(
+        &mut app,
+        &unique("Tin"),
+        &[("ore_primary", &material), ("ore_between", &material)],
+    )
+    .await;
+    search(&mut app, "name=").await;
+
+    let response = app.get("/metrics").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let metrics = response.body;
+    // 鉱石名は小文字にまとめ、同じ鉱脈は1回だけ数える
+    assert!(metrics.contains(&format!(
+        "gt6_vein_manager_veins{{material=\"{}\",state=\"total\"}} 1",
+        material.to_lowercase()
+    )));
+    assert!(metrics.contains(
+        "gt6_vein_manager_http_requests_total{method=\"GET\",route=\"/search\",status=\"200\"} 1"
+    ));
+    assert!(metrics.contains("gt6_vein_manager_active_sessions"));
+    assert!(metrics.contains("gt6_vein_manager_db_pool_connections{state=\"max\"}"));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn health_endpoints_answer_without_login() {
+    let mut app = TestApp::spawn().await;
+
+    let health = app.get("/healthz").await;
+    assert_eq!(health.status, StatusCode::OK);
+    assert_eq!(health.body, "ok");
+
+    let ready = app.get("/readyz").await;
+    assert_eq!(ready.status, StatusCode::OK);
+    assert_eq!(ready.body, "ready");
+}
+
+/// Handles of the sessions that can be revoked on the sessions page.
+fn session_handles(html: &str) -> Vec<String> {
+    html.split("name=\"handle\" value=\"")
+        .skip(1)
+        .map(|rest| rest[..rest.find('"').unwrap()].to_string())
+        .collect()
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn users_can_log_out_their_other_devices() {
+    let mut app = TestApp::spawn().await;
+    let username = app.login_as_new_user().await;
+    app.get("/").await;
+    let first_device = app.swap_cookies(Default::default());
+    app.login(&username, PASSWORD).await.assert_redirect("/");
+    app.get("/").await;
+    let second_device = app.swap_cookies(Default::default());
+    app.login(&username, PASSWORD).await.assert_redirect("/");
+    app.get("/").await;
+
+    let page = app.get("/auth/sessions").await;
+    assert_eq!(page.status, StatusCode::OK);
+    assert!(page.body.contains("127.0.0.1"));
+    let handles = session_handles(&page.body);
+    assert_eq!(
+        handles.len(),
+        2,
+        "the current session can not be revoked here"
+    );
+
+    app.post_form("/auth/sessions/revoke", &[("handle", &handles[0])])
+        .await
+        .assert_redirect("/auth/sessions");
+    assert_eq!(
+        session_handles(&app.get("/auth/sessions").await.body).len(),
+        1
+    );
+    let unknown = app
+        .post_form("/auth/sessions/revoke", &[("handle", "unknown")])
+        .await;
+    assert_eq!(unknown.status, StatusCode::NOT_FOUND);
+
+    app.post_form("/auth/sessions/revoke-others", &[])
+        .await
+        .assert_redirect("/auth/sessions");
+    assert!(session_handles(&app.get("/auth/sessions").await.body).is_empty());
+    assert_eq!(app.get("/").await.status, StatusCode::OK);
+
+    for device in [first_device, second_device] {
+        app.swap_cookies(device);
+        app.get("/").await.assert_redirect("/auth/login");
+    }
+}
@@ -0,0 +1,269 @@
+//! Harness that runs the whole application from `create_app` against a throwaway database.
+//!
+//! The database is taken from `TEST_DATABASE_URL` (any supported scheme). Without it a
+//! temporary SQLite file is used, which needs the `sqlite` feature; the tests are ignored
+//! without that feature, and fail if run anyway.
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::extract::connect_info::MockConnectInfo;
+use axum::http::{Request, StatusCode, header};
+use gt6_vein_manager::app::create_app;
+use gt6_vein_manager::auth::queries::AuthQueries;
+use gt6_vein_manager::auth::repository::SqlAuthRepository;
+use gt6_vein_manager::auth::session_store::RecentActivity;
+use gt6_vein_manager::auth::throttle::LoginThrottle;
+use gt6_vein_manager::config::{AppConfig, ConfigArgs};
+use gt6_vein_manager::database::connection::{AppState, DieselPool, create_diesel_pool};
+use gt6_vein_manager::database::migrations::{MigrationStatus, run_pending_migrations};
+use gt6_vein_manager::database::repository::SqlVeinRepository;
+use gt6_vein_manager::handlers::static_files::StaticAssets;
+use gt6_vein_manager::metrics::Metrics;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+pub const PASSWORD: &str = "password123";
+
+/// The running application together with the cookies of one browser.
+pub struct TestApp {
+    router: Router,
+    pub pool: DieselPool,
+    pub config: Arc<AppConfig>,
+    cookies: HashMap<String, String>,
+    sqlite_file: Option<PathBuf>,
+}
+
+/// A response with its body read into a string.
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub location: Option<String>,
+    pub body: String,
+}
+
+impl TestResponse {
+    /// Panics unless the response redirects to `to`.
+    pub fn assert_redirect(&self, to: &str) {
+        assert!(
+            self.status.is_redirection(),
+            "expected a redirect to {}, got {} with body {}",
+            to,
+            self.status,
+            self.body
+        );
+        assert_eq!(self.location.as_deref(), Some(to));
+    }
+}
+
+impl TestApp {
+    /// Starts the app on a fresh database. Panics when no database is available.
+    pub async fn spawn() -> TestApp {
+        let (database_url, sqlite_file) = match std::env::var("TEST_DATABASE_URL") {
+            Ok(url) => (url, None),
+            Err(_) => temporary_sqlite(),
+        };
+
+        run_pending_migrations(&database_url).await.unwrap();
+        let config = Arc::new(
+            AppConfig::load(ConfigArgs {
+                database_url: Some(database_url),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let pool = create_diesel_pool(&config).await.unwrap();
+        let state = AppState {
+            config: config.clone(),
+            diesel_pool: pool.clone(),
+            migration_status: MigrationStatus::new(&config.database_url),
+            vein_repository: Arc::new(SqlVeinRepository::new(pool.clone())),
+            auth_repository: Arc::new(SqlAuthRepository::new(pool.clone())),
+            login_throttle: LoginThrottle::new(),
+            recent_activity: RecentActivity::new(),
+            static_assets: StaticAssets::new(None),
+            metrics: Metrics::new(),
+        };
+
+        // ログイン処理は接続元の IP を参照するため、固定のアドレスを与える
+        let router = create_app(state)
+            .await
+            .unwrap()
+            .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))));
+
+        TestApp {
+            router,
+            pool,
+            config,
+            cookies: HashMap::new(),
+            sqlite_file,
+        }
+    }
+
+    pub async fn get(&mut self, path: &str) -> TestResponse {
+        let request = Request::get(path).body(Body::empty()).unwrap();
+        self.send(request).await
+    }
+
+    /// Requests a path as an API client that prefers JSON.
+    pub async fn get_json(&mut self, path: &str) -> TestResponse {
+        let request = Request::get(path)
+            .header(header::ACCEPT, "application/json")
+            .body(Body::empty())
+            .unwrap();
+        self.send(request).await
+    }
+
+    /// Submits a form the way the browser would, including the CSRF token of the session.
+    pub async fn post_form(&mut self, path: &str, fields: &[(&str, &str)]) -> TestResponse {
+        let token = self.csrf_token().await;
+        let mut pairs = fields.to_vec();
+        pairs.push(("csrf_token", &token));
+        self.post_raw(path, &pairs).await
+    }
+
+    /// Submits a form without adding anything to it.
+    pub async fn post_raw(&mut self, path: &str, fields: &[(&str, &str)]) -> TestResponse {
+        let request = Request::post(path)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(serde_urlencoded::to_string(fields).unwrap()))
+            .unwrap();
+        self.send(request).await
+    }
+
+    /// Reads the token from the hidden field every page renders.
+    pub async fn csrf_token(&mut self) -> String {
+        let page = self.get("/auth/login").await;
+        let marker = r#"name="csrf_token" value=""#;
+        let start = page
+            .body
+            .find(marker)
+            .expect("the login page renders a CSRF token")
+            + marker.len();
+        let end = start + page.body[start..].find('"').unwrap();
+        page.body[start..end].to_string()
+    }
+
+    /// Issues an invitation from the database, like the first start or `issue-invite` does.
+    pub async fn system_invitation(&self) -> String {
+        let mut connection = self.pool.get().await.unwrap();
+        AuthQueries::create_system_invitation(&mut connection, 1)
+            .await
+            .unwrap()
+            .token
+    }
+
+    /// Registers a new account through the invitation form and returns its name.
+    pub async fn register(&mut self, prefix: &str) -> String {
+        let token = self.system_invitation().await;
+        let username = unique(prefix);
+        self.post_form(
+            "/auth/register",
+            &[
+                ("username", &username),
+                ("password", PASSWORD),
+                ("token", &token),
+            ],
+        )
+        .await
+        .assert_redirect("/auth/login");
+        username
+    }
+
+    pub async fn login(&mut self, username: &str, password: &str) -> TestResponse {
+        self.post_form(
+            "/auth/login",
+            &[("username", username), ("password", password)],
+        )
+        .await
+    }
+
+    /// Registers and logs in a fresh account.
+    pub async fn login_as_new_user(&mut self) -> String {
+        let username = self.register("user").await;
+        self.login(&username, PASSWORD).await.assert_redirect("/");
+        username
+    }
+
+    /// Replaces the cookie jar and returns the old one, to act as another browser.
+    pub fn swap_cookies(&mut self, cookies: HashMap<String, String>) -> HashMap<String, String> {
+        std::mem::replace(&mut self.cookies, cookies)
+    }
+
+    async fn send(&mut self, mut request: Request<Body>) -> TestResponse {
+        if !self.cookies.is_empty() {
+            let cookie = self
+                .cookies
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<_>>()
+                .join("; ");
+            request
+                .headers_mut()
+                .insert(header::COOKIE, cookie.parse().unwrap());
+        }
+
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        for set_cookie in response.headers().get_all(header::SET_COOKIE) {
+            self.store_cookie(set_cookie.to_str().unwrap());
+        }
+
+        let status = response.status();
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .map(|value| value.to_str().unwrap().to_string());
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        TestResponse {
+            status,
+            location,
+            body: String::from_utf8(bytes.to_vec()).unwrap(),
+        }
+    }
+
+    fn store_cookie(&mut self, set_cookie: &str) {
+        let mut attributes = set_cookie.split(';').map(str::trim);
+        let Some((name, value)) = attributes.next().and_then(|pair| pair.split_once('=')) else {
+            return;
+        };
+        let removed = attributes.any(|attribute| attribute.eq_ignore_ascii_case("Max-Age=0"));
+        if removed {
+            self.cookies.remove(name);
+        } else {
+            self.cookies.insert(name.to_string(), value.to_string());
+        }
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        if let Some(path) = &self.sqlite_file {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+            }
+        }
+    }
+}
+
+/// URL of a new SQLite file in the temporary directory, and the file to delete afterwards.
+#[cfg(feature = "sqlite")]
+pub fn temporary_sqlite() -> (String, Option<PathBuf>) {
+    let path = std::env::temp_dir().join(format!("gt6-vein-manager-{}.db", Uuid::new_v4()));
+    (format!("sqlite://{}", path.display()), Some(path))
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn temporary_sqlite() -> (String, Option<PathBuf>) {
+    panic!("set TEST_DATABASE_URL, or run the tests with `cargo test --features sqlite`");
+}
+
+/// Suffix so that repeated runs against the same database do not collide.
+pub fn unique(prefix: &str) -> String {
+    format!("{}_{}", prefix, &Uuid::new_v4().simple().to_string()[..12])
+}
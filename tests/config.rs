@@ -0,0 +1,41 @@
+//! Settings derived from the configuration.
+
+use std::net::SocketAddr;
+
+use axum::http::HeaderMap;
+use gt6_vein_manager::config::{AppConfig, ConfigArgs};
+
+fn config(trusted_proxy_header: Option<&str>) -> anyhow::Result<AppConfig> {
+    AppConfig::load(ConfigArgs {
+        database_url: Some("sqlite://unused.db".to_string()),
+        port: Some(24528),
+        trusted_proxy_header: trusted_proxy_header.map(str::to_string),
+        ..Default::default()
+    })
+}
+
+#[test]
+fn client_ip_comes_from_the_trusted_proxy_header_only() {
+    let peer = SocketAddr::from(([10, 0, 0, 2], 50000));
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "x-forwarded-for",
+        "203.0.113.9, 198.51.100.7".parse().unwrap(),
+    );
+
+    // 設定がなければヘッダーは偽装できるため使わない
+    let direct = config(None).unwrap();
+    assert_eq!(direct.client_ip(&headers, peer), peer.ip());
+
+    // プロキシが最後に追加したアドレスを使う
+    let proxied = config(Some("X-Forwarded-For")).unwrap();
+    assert_eq!(
+        proxied.client_ip(&headers, peer),
+        "198.51.100.7".parse::<std::net::IpAddr>().unwrap()
+    );
+    assert_eq!(proxied.client_ip(&HeaderMap::new(), peer), peer.ip());
+    headers.insert("x-forwarded-for", "unknown".parse().unwrap());
+    assert_eq!(proxied.client_ip(&headers, peer), peer.ip());
+
+    assert!(config(Some("not a header")).is_err());
+}
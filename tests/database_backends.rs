@@ -0,0 +1,335 @@
+//! The same checks of the data layer, run against every database backend.
+//!
+//! MySQL and PostgreSQL need an empty throwaway database given by `TEST_MYSQL_URL` /
+//! `TEST_POSTGRES_URL`; those tests are ignored unless run with `--include-ignored`.
+//! SQLite runs on a temporary file whenever the `sqlite` feature is enabled.
+
+use std::collections::HashMap;
+
+use gt6_vein_manager::auth::queries::AuthQueries;
+use gt6_vein_manager::auth::session_store::DieselSessionStore;
+use gt6_vein_manager::config::{AppConfig, ConfigArgs};
+use gt6_vein_manager::database::connection::{DieselPool, create_diesel_pool};
+use gt6_vein_manager::database::migrations::{MigrationStatus, run_pending_migrations};
+use gt6_vein_manager::database::queries::{
+    VeinWithStatus, insert_vein, insert_vein_confirmation, insert_vein_note,
+    insert_vein_revocation, search_veins,
+};
+use gt6_vein_manager::models::forms::{NoteScope, SearchQuery};
+use tower_sessions::session::{Id, Record};
+use tower_sessions::{ExpiredDeletion, SessionStore};
+use uuid::Uuid;
+
+async fn setup(database_url: &str) -> DieselPool {
+    run_pending_migrations(database_url).await.unwrap();
+    // 二回目は何も適用されない
+    assert!(
+        run_pending_migrations(database_url)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(MigrationStatus::new(database_url).check().await, Ok(()));
+
+    let config = AppConfig::load(ConfigArgs {
+        database_url: Some(database_url.to_string()),
+        ..Default::default()
+    })
+    .unwrap();
+    create_diesel_pool(&config).await.unwrap()
+}
+
+/// Suffix so that repeated runs against the same database do not collide.
+fn unique(prefix: &str) -> String {
+    format!("{}_{}", prefix, &Uuid::new_v4().simple().to_string()[..12])
+}
+
+async fn exercise_users_and_invitations(pool: &DieselPool) {
+    let mut connection = pool.get().await.unwrap();
+    let username = unique("user");
+
+    let user = AuthQueries::create_user(
+        &mut connection,
+        &username,
+        Some("user@example.com"),
+        "password123",
+        None,
+        true,
+    )
+    .await
+    .unwrap();
+    assert!(AuthQueries::has_any_users(&mut connection).await.unwrap());
+
+    let found = AuthQueries::get_user_by_username(&mut connection, &username)
+        .await
+        .unwrap()
+        .expect("created user is found by name");
+    assert_eq!(found.id, user.id);
+    assert_eq!(found.is_admin, Some(true));
+
+    AuthQueries::update_password(&mut connection, &user.id, "another-password")
+        .await
+        .unwrap();
+    let updated = AuthQueries::get_user_by_id(&mut connection, &user.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_ne!(updated.password_hash, user.password_hash);
+
+    let invitation = AuthQueries::create_invitation(&mut connection, None, Some(&user.id), 1)
+        .await
+        .unwrap();
+    assert!(
+        AuthQueries::get_invitation_by_token(&mut connection, &invitation.token)
+            .await
+            .unwrap()
+            .is_some()
+    );
+    AuthQueries::mark_invitation_used(&mut connection, &invitation.token, &user.id)
+        .await
+        .unwrap();
+    assert!(
+        AuthQueries::get_invitation_by_token(&mut connection, &invitation.token)
+            .await
+            .unwrap()
+            .is_none(),
+        "a used invitation can not be used again"
+    );
+}
+
+async fn exercise_veins(pool: &DieselPool) {
+    let mut connection = pool.get().await.unwrap();
+    let name = unique("Magnetite");
+    let id = Uuid::new_v4().to_string();
+
+    insert_vein(
+        &mut connection,
+        &id,
+        &name,
+        100,
+        Some((32, 48)),
+        -200,
+        &Some("near spawn".to_string()),
+    )
+    .await
+    .unwrap();
+    insert_vein_confirmation(&mut connection, &id, true)
+        .await
+        .unwrap();
+
+    // 名前検索は大文字小文字を区別しない
+    let query = SearchQuery {
+        name: Some(name.to_uppercase()),
+        ..Default::default()
+    };
+    let veins = search_veins(&mut connection, &query).await.unwrap();
+    assert_eq!(veins.len(), 1);
+    let vein = &veins[0];
+    assert_eq!(vein.id, id);
+    assert_eq!(
+        (vein.x_coord, vein.y_min, vein.y_max, vein.z_coord),
+        (100, Some(32), Some(48), -200)
+    );
+    assert_eq!(vein.notes.as_deref(), Some("near spawn"));
+    assert!(vein.confirmed);
+    assert!(!vein.depleted);
+    assert!(vein.created_at.is_some());
+
+    // キーワードは名前と最新のメモ、または過去のすべてのメモから探す
+    insert_vein_note(&mut connection, &id, "Needs Silk Touch")
+        .await
+        .unwrap();
+    let by_text = |text: &str, note_scope| SearchQuery {
+        text: Some(text.to_string()),
+        note_scope: Some(note_scope),
+        ..Default::default()
+    };
+    let found = |veins: Vec<VeinWithStatus>| veins.iter().any(|vein| vein.id == id);
+    let veins = search_veins(&mut connection, &by_text("silk", NoteScope::Latest))
+        .await
+        .unwrap();
+    assert!(found(veins));
+    let query_both = format!("{} touch", name.to_lowercase());
+    let veins = search_veins(&mut connection, &by_text(&query_both, NoteScope::Latest))
+        .await
+        .unwrap();
+    assert!(found(veins));
+    let veins = search_veins(
+        &mut connection,
+        &by_text(&format!("{} spawn", name), NoteScope::Latest),
+    )
+    .await
+    .unwrap();
+    assert!(!found(veins));
+    let veins = search_veins(
+        &mut connection,
+        &by_text(&format!("{} spawn", name), NoteScope::All),
+    )
+    .await
+    .unwrap();
+    assert!(found(veins));
+
+    // 高さの範囲は一部でも重なれば一致する
+    for (y_from, y_to, expected) in [
+        (Some(48), None, true),
+        (None, Some(32), true),
+        (Some(40), Some(40), true),
+        (Some(49), Some(60), false),
+        (Some(0), Some(31), false),
+    ] {
+        let within = SearchQuery {
+            name: Some(name.clone()),
+            y_from,
+            y_to,
+            ..Default::default()
+        };
+        let veins = search_veins(&mut connection, &within).await.unwrap();
+        assert_eq!(found(veins), expected, "Y {:?}..{:?}", y_from, y_to);
+    }
+
+    insert_vein_revocation(&mut connection, &id, true)
+        .await
+        .unwrap();
+    assert!(
+        search_veins(&mut connection, &query)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    let with_revoked = SearchQuery {
+        name: Some(name),
+        include_revoked: Some(true),
+        ..Default::default()
+    };
+    assert_eq!(
+        search_veins(&mut connection, &with_revoked)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+async fn exercise_session_store(pool: &DieselPool) {
+    let store = DieselSessionStore::new(pool.clone());
+    let mut data = HashMap::new();
+    data.insert("key".to_string(), serde_json::json!("value"));
+
+    let mut record = Record {
+        id: Id::default(),
+        data,
+        expiry_date: time::OffsetDateTime::now_utc() + time::Duration::hours(1),
+    };
+    store.create(&mut record).await.unwrap();
+    let loaded = store
+        .load(&record.id)
+        .await
+        .unwrap()
+        .expect("session is stored");
+    assert_eq!(loaded.data, record.data);
+
+    record
+        .data
+        .insert("key".to_string(), serde_json::json!("changed"));
+    store.save(&record).await.unwrap();
+    let loaded = store.load(&record.id).await.unwrap().unwrap();
+    assert_eq!(loaded.data["key"], "changed");
+
+    // ログイン中の端末として記録し、一覧から他の端末を削除できる
+    let user_id = Uuid::new_v4().to_string();
+    let mut other = Record {
+        id: Id::default(),
+        data: HashMap::new(),
+        expiry_date: record.expiry_date,
+    };
+    store.create(&mut other).await.unwrap();
+    for id in [&record.id, &other.id] {
+        store
+            .record_activity(id, &user_id, Some("Firefox"), "192.0.2.1")
+            .await
+            .unwrap();
+    }
+    let listed = store.list_user_sessions(&user_id).await.unwrap();
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0].user_agent.as_deref(), Some("Firefox"));
+    assert_eq!(listed[0].ip_address.as_deref(), Some("192.0.2.1"));
+    assert!(listed[0].last_seen_at.is_some());
+    // 他人のセッションは削除できない
+    assert_eq!(
+        store
+            .delete_user_session("someone-else", &other.id.to_string())
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        store
+            .delete_other_user_sessions(&user_id, &record.id.to_string())
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(store.load(&other.id).await.unwrap().is_none());
+
+    store.delete(&record.id).await.unwrap();
+    assert!(store.load(&record.id).await.unwrap().is_none());
+
+    let mut expired = Record {
+        id: Id::default(),
+        data: HashMap::new(),
+        expiry_date: time::OffsetDateTime::now_utc() - time::Duration::hours(1),
+    };
+    store.create(&mut expired).await.unwrap();
+    let active = store.count_active_sessions().await.unwrap();
+    // 期限切れのセッションは削除タスクと同じフックで消える
+    store.delete_expired().await.unwrap();
+    assert!(store.load(&expired.id).await.unwrap().is_none());
+    assert_eq!(store.count_active_sessions().await.unwrap(), active);
+
+    // 定期削除のタスクは停止すると二度と動かない
+    // load は期限切れの行を消してしまうため、残っている行は delete_expired_sessions の件数で確かめる
+    let purge = store
+        .clone()
+        .spawn_expired_deletion(std::time::Duration::from_millis(10));
+    store.create(&mut expired).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert_eq!(store.delete_expired_sessions().await.unwrap(), 0);
+    purge.stop().await;
+    store.create(&mut expired).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert_eq!(store.delete_expired_sessions().await.unwrap(), 1);
+}
+
+async fn exercise_backend(database_url: &str) {
+    let pool = setup(database_url).await;
+    exercise_users_and_invitations(&pool).await;
+    exercise_veins(&pool).await;
+    exercise_session_store(&pool).await;
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_MYSQL_URL"]
+async fn mysql_backend() {
+    let database_url = std::env::var("TEST_MYSQL_URL").expect("TEST_MYSQL_URL is not set");
+    exercise_backend(&database_url).await;
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+#[ignore = "needs TEST_POSTGRES_URL"]
+async fn postgres_backend() {
+    let database_url = std::env::var("TEST_POSTGRES_URL").expect("TEST_POSTGRES_URL is not set");
+    exercise_backend(&database_url).await;
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn sqlite_backend() {
+    let path = std::env::temp_dir().join(format!("gt6-vein-manager-{}.db", Uuid::new_v4()));
+    let database_url = format!("sqlite://{}", path.display());
+
+    // 空のデータベースはマイグレーションが未適用として報告される
+    assert!(MigrationStatus::new(&database_url).check().await.is_err());
+    exercise_backend(&database_url).await;
+    let _ = std::fs::remove_file(path);
+}
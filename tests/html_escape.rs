@@ -0,0 +1,166 @@
+use askama::Template;
+use chrono::Utc;
+use gt6_vein_manager::auth::throttle::{LockoutEntry, ThrottleKey};
+use gt6_vein_manager::database::queries::VeinWithStatus;
+use gt6_vein_manager::models::forms::SearchQuery;
+use gt6_vein_manager::templates::{
+    CurrentUser, Flash, FlashLevel, Layout, LockoutsTemplate, RegisterTemplate,
+    SearchResultsTemplate,
+};
+
+fn layout() -> Layout {
+    Layout {
+        user: None,
+        csrf_token: "token".to_string(),
+        flashes: Vec::new(),
+    }
+}
+
+fn vein_named(name: &str, notes: Option<&str>) -> VeinWithStatus {
+    VeinWithStatus {
+        id: "00000000-0000-0000-0000-000000000000".to_string(),
+        name: name.to_string(),
+        x_coord: 0,
+        y_min: None,
+        y_max: None,
+        z_coord: 0,
+        notes: notes.map(|n| n.to_string()),
+        created_at: None,
+        confirmed: false,
+        depleted: false,
+        revoked: false,
+        is_bedrock: false,
+        ores: Vec::new(),
+    }
+}
+
+fn render_results(veins: Vec<VeinWithStatus>, query: &SearchQuery) -> String {
+    SearchResultsTemplate {
+        layout: layout(),
+        search_info: query.name.clone().unwrap_or_default(),
+        total: veins.len(),
+        favorite_ids: Vec::new(),
+        veins,
+        query_state: query.get_all_query_string(),
+        query: query.clone(),
+    }
+    .render()
+    .unwrap()
+}
+
+#[test]
+fn vein_name_and_notes_cannot_inject_script() {
+    let veins = vec![vein_named(
+        "<script>alert(1)</script>",
+        Some("<img src=x onerror=alert(2)>"),
+    )];
+    let query = SearchQuery::default();
+
+    let html = render_results(veins, &query);
+
+    assert!(!html.contains("<script>"));
+    assert!(!html.contains("<img"));
+    assert!(html.contains("&#60;script&#62;alert(1)&#60;/script&#62;"));
+    assert!(html.contains("&#60;img src=x onerror=alert(2)&#62;"));
+}
+
+#[test]
+fn search_term_cannot_break_out_of_markup() {
+    let query = SearchQuery {
+        name: Some(r#""><script>alert(1)</script>"#.to_string()),
+        include_revoked: Some(true),
+        ..Default::default()
+    };
+
+    let html = render_results(vec![vein_named("Iron", None)], &query);
+
+    assert!(!html.contains("<script>"));
+    assert!(!html.contains(r#""><"#));
+}
+
+#[test]
+fn invitation_token_cannot_break_out_of_hidden_input() {
+    let html = RegisterTemplate {
+        layout: layout(),
+        token: Some(r#"x" autofocus onfocus="alert(1)"#.to_string()),
+    }
+    .render()
+    .unwrap();
+
+    assert!(!html.contains(r#"onfocus="alert(1)""#));
+}
+
+#[test]
+fn locked_username_cannot_inject_into_admin_page() {
+    let html = LockoutsTemplate {
+        layout: layout(),
+        lockouts: vec![LockoutEntry {
+            key: ThrottleKey::new(
+                "192.0.2.1".parse().unwrap(),
+                r#""><script>alert(1)</script>"#,
+            ),
+            failures: 5,
+            last_failure: Utc::now(),
+            locked_until: Utc::now(),
+        }],
+    }
+    .render()
+    .unwrap();
+
+    assert!(!html.contains("<script>"));
+    assert!(!html.contains(r#""><"#));
+}
+
+#[test]
+fn username_and_flash_messages_are_escaped() {
+    let html = RegisterTemplate {
+        layout: Layout {
+            user: Some(CurrentUser {
+                username: "<b>admin</b>".to_string(),
+                is_admin: true,
+            }),
+            csrf_token: "token".to_string(),
+            flashes: vec![Flash {
+                level: FlashLevel::Success,
+                message: "「<script>alert(1)</script>」が正常に追加されました！".to_string(),
+            }],
+        },
+        token: None,
+    }
+    .render()
+    .unwrap();
+
+    assert!(!html.contains("<b>admin</b>"));
+    assert!(!html.contains("<script>"));
+}
+
+#[test]
+fn query_string_is_url_encoded() {
+    let query = SearchQuery {
+        name: Some("a&include_revoked=true #".to_string()),
+        include_revoked: Some(false),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        query.get_all_query_string(),
+        "name=a%26include_revoked%3Dtrue+%23&include_revoked=false"
+    );
+}
+
+#[test]
+fn highlighted_keywords_are_still_escaped() {
+    let query = SearchQuery {
+        text: Some("iron".to_string()),
+        ..Default::default()
+    };
+
+    let html = render_results(
+        vec![vein_named("<b>Iron</b>", Some("<script>iron</script>"))],
+        &query,
+    );
+
+    assert!(html.contains("&#60;b&#62;<mark>Iron</mark>&#60;/b&#62;"));
+    assert!(html.contains("&#60;script&#62;<mark>iron</mark>&#60;/script&#62;"));
+    assert!(!html.contains("<script>"));
+}
@@ -0,0 +1,22 @@
+use gt6_vein_manager::logging::redact_query;
+
+#[test]
+fn sensitive_parameters_are_redacted() {
+    assert_eq!(
+        redact_query("token=abc123&next=%2F"),
+        "token=[REDACTED]&next=%2F"
+    );
+    assert_eq!(
+        redact_query("username=alice&password=secret"),
+        "username=alice&password=[REDACTED]"
+    );
+}
+
+#[test]
+fn other_parameters_are_kept() {
+    assert_eq!(
+        redact_query("name=copper&include_revoked=true"),
+        "name=copper&include_revoked=true"
+    );
+    assert_eq!(redact_query("flag"), "flag");
+}
@@ -0,0 +1,83 @@
+//! Ore compositions, the GT6 vein definitions and the material index.
+
+use gt6_vein_manager::ores::{
+    LARGE_VEINS_TSV, OreRole, VeinOre, large_veins, material_index, normalize_composition,
+    parse_vein_definitions,
+};
+
+#[test]
+fn compositions_are_sorted_by_role_without_repeats() {
+    let mut ores = vec![
+        VeinOre::new("Tin", OreRole::Sporadic),
+        VeinOre::new("Cassiterite", OreRole::Between),
+        VeinOre::new("Tin", OreRole::Primary),
+        VeinOre::new("Tin", OreRole::Sporadic),
+    ];
+    normalize_composition(&mut ores);
+    assert_eq!(
+        ores,
+        [
+            VeinOre::new("Tin", OreRole::Primary),
+            VeinOre::new("Cassiterite", OreRole::Between),
+            VeinOre::new("Tin", OreRole::Sporadic),
+        ]
+    );
+    assert_eq!("between".parse::<OreRole>(), Ok(OreRole::Between));
+    assert!("middle".parse::<OreRole>().is_err());
+}
+
+#[test]
+fn vein_definitions_are_read_from_tab_separated_lines() {
+    let definitions =
+        parse_vein_definitions("# comment\n\nSample\tAlpha\tBeta\tGamma\tAlpha\n").unwrap();
+    assert_eq!(definitions.len(), 1);
+    assert_eq!(definitions[0].name, "Sample");
+    assert_eq!(
+        definitions[0].composition(),
+        [
+            VeinOre::new("Alpha", OreRole::Primary),
+            VeinOre::new("Beta", OreRole::Secondary),
+            VeinOre::new("Gamma", OreRole::Between),
+            VeinOre::new("Alpha", OreRole::Sporadic),
+        ]
+    );
+    assert!(definitions[0].yields(" gamma "));
+    assert!(!definitions[0].yields("Delta"));
+
+    for invalid in [
+        "Sample\tAlpha\tBeta\tGamma\n",
+        "Sample\tAlpha\t\tGamma\tDelta\n",
+        "Sample\tAlpha\tBeta\tGamma\tDelta\nsample\tAlpha\tBeta\tGamma\tDelta\n",
+    ] {
+        assert!(parse_vein_definitions(invalid).is_err(), "{:?}", invalid);
+    }
+}
+
+#[test]
+fn bundled_vein_definitions_are_well_formed() {
+    let definitions = parse_vein_definitions(LARGE_VEINS_TSV).unwrap();
+    assert_eq!(large_veins(), definitions.as_slice());
+}
+
+#[test]
+fn material_index_lists_known_and_recorded_materials() {
+    let definitions = parse_vein_definitions(
+        "Sample\tAlpha\tBeta\tGamma\tAlpha\nOther\tDelta\tDelta\tBeta\tDelta\n",
+    )
+    .unwrap();
+    let index = material_index(
+        &definitions,
+        [
+            "beta".to_string(),
+            "Unobtainium".to_string(),
+            "unobtainium".to_string(),
+        ],
+    );
+
+    // 定義にある鉱石は定義の表記で、記録だけの鉱石は最初の表記で一度だけ載る
+    let names: Vec<&str> = index.iter().map(|entry| entry.material.as_str()).collect();
+    assert_eq!(names, ["Alpha", "Beta", "Delta", "Gamma", "Unobtainium"]);
+    assert_eq!(index[1].vein_types, ["Sample", "Other"]);
+    assert!(index[4].vein_types.is_empty());
+    assert!(material_index(&[], Vec::new()).is_empty());
+}
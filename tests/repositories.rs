@@ -0,0 +1,691 @@
+//! The repository traits behave the same in memory and in the database.
+//!
+//! The in-memory implementations always run. The SQL ones use `TEST_DATABASE_URL`, or a
+//! temporary SQLite file with the `sqlite` feature, and are ignored without either.
+
+mod common;
+
+use std::sync::Arc;
+
+use gt6_vein_manager::auth::repository::{
+    AuthRepository, InMemoryAuthRepository, SqlAuthRepository,
+};
+use gt6_vein_manager::config::{AppConfig, ConfigArgs};
+use gt6_vein_manager::database::connection::create_diesel_pool;
+use gt6_vein_manager::database::migrations::run_pending_migrations;
+use gt6_vein_manager::database::queries::VeinWithStatus;
+use gt6_vein_manager::database::repository::{
+    InMemoryVeinRepository, SqlVeinRepository, VeinCount, VeinRepository, VeinStatus,
+};
+use gt6_vein_manager::models::forms::{SearchQuery, SortKey, SortOrder};
+use gt6_vein_manager::ores::{OreRole, VeinOre};
+use uuid::Uuid;
+
+fn unique(prefix: &str) -> String {
+    format!("{}_{}", prefix, &Uuid::new_v4().simple().to_string()[..12])
+}
+
+fn by_name(name: &str, include_revoked: bool) -> SearchQuery {
+    SearchQuery {
+        name: Some(name.to_string()),
+        include_revoked: Some(include_revoked),
+        ..Default::default()
+    }
+}
+
+async fn exercise_veins(repository: Arc<dyn VeinRepository>) {
+    let name = unique("Galena");
+    let id = Uuid::new_v4().to_string();
+
+    repository
+        .insert_vein(&id, &name, 10, None, -20, &Some("by the river".to_string()))
+        .await
+        .unwrap();
+    let duplicate = repository
+        .insert_vein(&id, &name, 10, None, -20, &None)
+        .await
+        .unwrap_err();
+    assert!(duplicate.is_unique_violation());
+
+    let veins = repository
+        .search_veins(&by_name(&name.to_uppercase(), false))
+        .await
+        .unwrap();
+    assert_eq!(veins.len(), 1);
+    assert_eq!(veins[0].notes.as_deref(), Some("by the river"));
+    assert!(!veins[0].confirmed && !veins[0].is_bedrock);
+    let by_text = SearchQuery {
+        text: Some(format!("RIVER {}", name)),
+        ..Default::default()
+    };
+    assert_eq!(repository.search_veins(&by_text).await.unwrap().len(), 1);
+    let by_text = SearchQuery {
+        text: Some(format!("lake {}", name)),
+        ..Default::default()
+    };
+    assert!(repository.search_veins(&by_text).await.unwrap().is_empty());
+
+    repository
+        .record_status(&id, VeinStatus::Confirmation, true)
+        .await
+        .unwrap();
+    repository
+        .record_status(&id, VeinStatus::IsBedrock, true)
+        .await
+        .unwrap();
+    repository
+        .record_status(&id, VeinStatus::Confirmation, false)
+        .await
+        .unwrap();
+    let vein = &repository
+        .search_veins(&by_name(&name, false))
+        .await
+        .unwrap()[0];
+    assert!(!vein.confirmed);
+    assert!(vein.is_bedrock);
+    // 鉱石ごとに数え、役割が違っても同じ鉱脈は1回だけ数える
+    let material = unique("Lead");
+    repository
+        .set_composition(
+            &id,
+            &[
+                VeinOre::new(&material, OreRole::Primary),
+                VeinOre::new(material.to_uppercase(), OreRole::Sporadic),
+            ],
+        )
+        .await
+        .unwrap();
+    let counts = repository.count_veins(usize::MAX).await.unwrap();
+    assert!(counts.contains(&VeinCount {
+        material: material.to_lowercase(),
+        total: 1,
+        confirmed: 0,
+        depleted: 0,
+    }));
+    assert!(counts.windows(2).all(|pair| pair[0].total >= pair[1].total));
+    assert!(repository.count_veins(1).await.unwrap().len() <= 1);
+
+    repository
+        .record_status(&id, VeinStatus::Revocation, true)
+        .await
+        .unwrap();
+    assert!(
+        repository
+            .search_veins(&by_name(&name, false))
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        repository
+            .search_veins(&by_name(&name, true))
+            .await
+            .unwrap()[0]
+            .revoked
+    );
+    // 取り消された鉱脈は数えない
+    assert!(
+        !repository
+            .count_veins(usize::MAX)
+            .await
+            .unwrap()
+            .iter()
+            .any(|count| count.material == material.to_lowercase())
+    );
+
+    // 存在しない鉱脈の状態は記録できない
+    assert!(
+        repository
+            .record_status(&Uuid::new_v4().to_string(), VeinStatus::Depletion, true)
+            .await
+            .is_err()
+    );
+}
+
+async fn exercise_paging(repository: Arc<dyn VeinRepository>) {
+    let batch = unique("Page");
+    let mut ids = Vec::new();
+    for (suffix, x_coord, z_coord) in [("c", 0, 0), ("A", 300, 400), ("b", -30, 40)] {
+        let id = Uuid::new_v4().to_string();
+        repository
+            .insert_vein(
+                &id,
+                &format!("{}_{}", batch, suffix),
+                x_coord,
+                None,
+                z_coord,
+                &None,
+            )
+            .await
+            .unwrap();
+        ids.push(id);
+    }
+    let names = |veins: &[VeinWithStatus]| -> Vec<String> {
+        veins
+            .iter()
+            .map(|vein| vein.name.trim_start_matches(&batch).to_string())
+            .collect()
+    };
+    let query = |sort, order, page| SearchQuery {
+        name: Some(batch.clone()),
+        sort: Some(sort),
+        order: Some(order),
+        from_x: Some(300),
+        from_z: Some(400),
+        page: Some(page),
+        limit: Some(2),
+        ..Default::default()
+    };
+
+    // 名前順は大文字小文字を区別しない
+    let page = repository
+        .search_veins_page(&query(SortKey::Name, SortOrder::Asc, 1))
+        .await
+        .unwrap();
+    assert_eq!(page.total, 3);
+    assert_eq!(names(&page.veins), ["_A", "_b"]);
+    let page = repository
+        .search_veins_page(&query(SortKey::Name, SortOrder::Asc, 2))
+        .await
+        .unwrap();
+    assert_eq!(page.total, 3);
+    assert_eq!(names(&page.veins), ["_c"]);
+    assert!(
+        repository
+            .search_veins_page(&query(SortKey::Name, SortOrder::Asc, 3))
+            .await
+            .unwrap()
+            .veins
+            .is_empty()
+    );
+
+    let veins = repository
+        .search_veins(&query(SortKey::Distance, SortOrder::Asc, 1))
+        .await
+        .unwrap();
+    assert_eq!(names(&veins), ["_A", "_b", "_c"]);
+    let veins = repository
+        .search_veins(&query(SortKey::Name, SortOrder::Desc, 1))
+        .await
+        .unwrap();
+    assert_eq!(names(&veins), ["_c", "_b", "_A"]);
+
+    repository
+        .record_status(&ids[0], VeinStatus::Depletion, true)
+        .await
+        .unwrap();
+    repository
+        .record_status(&ids[2], VeinStatus::Confirmation, true)
+        .await
+        .unwrap();
+    let veins = repository
+        .search_veins(&query(SortKey::Status, SortOrder::Asc, 1))
+        .await
+        .unwrap();
+    assert_eq!(names(&veins), ["_b", "_A", "_c"]);
+}
+
+async fn exercise_filters(repository: Arc<dyn VeinRepository>) {
+    let batch = unique("Filter");
+    let mut ids = Vec::new();
+    for (suffix, y_range) in [
+        ("plain", None),
+        ("confirmed", Some((10, 20))),
+        ("bedrock", Some((1, 5))),
+    ] {
+        let id = Uuid::new_v4().to_string();
+        repository
+            .insert_vein(&id, &format!("{}_{}", batch, suffix), 0, y_range, 0, &None)
+            .await
+            .unwrap();
+        ids.push(id);
+    }
+    for status in [VeinStatus::Confirmation, VeinStatus::IsBedrock] {
+        repository
+            .record_status(&ids[2], status, true)
+            .await
+            .unwrap();
+    }
+    repository
+        .record_status(&ids[1], VeinStatus::Confirmation, true)
+        .await
+        .unwrap();
+
+    let search = |query: SearchQuery| {
+        let repository = repository.clone();
+        let batch = batch.clone();
+        async move {
+            let mut names: Vec<String> = repository
+                .search_veins(&SearchQuery {
+                    name: Some(batch.clone()),
+                    ..query
+                })
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|vein| vein.name.trim_start_matches(&batch).to_string())
+                .collect();
+            names.sort();
+            names
+        }
+    };
+
+    assert_eq!(
+        search(SearchQuery {
+            confirmed: Some(true),
+            bedrock: Some(false),
+            ..Default::default()
+        })
+        .await,
+        ["_confirmed"]
+    );
+    assert_eq!(
+        search(SearchQuery {
+            confirmed: Some(false),
+            ..Default::default()
+        })
+        .await,
+        ["_plain"]
+    );
+    // 高さの分からない鉱脈は、高さを指定すると一致しない
+    assert_eq!(
+        search(SearchQuery {
+            y_to: Some(5),
+            ..Default::default()
+        })
+        .await,
+        ["_bedrock"]
+    );
+    assert_eq!(
+        search(SearchQuery {
+            y_from: Some(5),
+            y_to: Some(12),
+            ..Default::default()
+        })
+        .await,
+        ["_bedrock", "_confirmed"]
+    );
+    assert_eq!(
+        search(SearchQuery {
+            confirmed: Some(true),
+            depleted: Some(false),
+            bedrock: Some(true),
+            ..Default::default()
+        })
+        .await,
+        ["_bedrock"]
+    );
+
+    // 登録日の範囲は両端の日を含む（データベースの時刻とずれないよう前後1日を取る）
+    let today = chrono::Utc::now().date_naive();
+    assert_eq!(
+        search(SearchQuery {
+            created_from: today.pred_opt(),
+            created_to: today.succ_opt(),
+            ..Default::default()
+        })
+        .await
+        .len(),
+        3
+    );
+    assert!(
+        search(SearchQuery {
+            created_to: today.pred_opt().and_then(|day| day.pred_opt()),
+            ..Default::default()
+        })
+        .await
+        .is_empty()
+    );
+
+    // % と _ はワイルドカードではなく、その文字自体を探す
+    let literal = unique("Literal");
+    for (suffix, note) in [("100%", "a_b"), ("1000", "axb")] {
+        repository
+            .insert_vein(
+                &Uuid::new_v4().to_string(),
+                &format!("{} {}", literal, suffix),
+                0,
+                None,
+                0,
+                &Some(note.to_string()),
+            )
+            .await
+            .unwrap();
+    }
+    let names = |veins: Vec<VeinWithStatus>| -> Vec<String> {
+        veins.into_iter().map(|vein| vein.name).collect()
+    };
+    assert_eq!(
+        names(
+            repository
+                .search_veins(&by_name(&format!("{} 100%", literal), false))
+                .await
+                .unwrap()
+        ),
+        [format!("{} 100%", literal)]
+    );
+    assert_eq!(
+        names(
+            repository
+                .search_veins(&SearchQuery {
+                    text: Some(format!("{} a_b", literal)),
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+        ),
+        [format!("{} 100%", literal)]
+    );
+    assert!(
+        repository
+            .search_veins(&SearchQuery {
+                text: Some(format!("{} \\", literal)),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+async fn exercise_saved_searches_and_favorites(
+    repository: Arc<dyn VeinRepository>,
+    user_id: &str,
+    other_user_id: &str,
+) {
+    let vein_id = Uuid::new_v4().to_string();
+    repository
+        .insert_vein(&vein_id, &unique("Pinned"), 0, None, 0, &None)
+        .await
+        .unwrap();
+
+    let iron = repository
+        .save_search(user_id, "iron", "name=iron")
+        .await
+        .unwrap();
+    repository
+        .save_search(user_id, "copper", "name=copper&confirmed=true")
+        .await
+        .unwrap();
+    assert!(
+        repository
+            .save_search(user_id, "iron", "name=iron2")
+            .await
+            .unwrap_err()
+            .is_unique_violation()
+    );
+    let searches = repository.list_saved_searches(user_id).await.unwrap();
+    let names: Vec<&str> = searches.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["copper", "iron"]);
+    assert_eq!(searches[0].query_string, "name=copper&confirmed=true");
+
+    assert!(
+        !repository
+            .delete_saved_search(other_user_id, &iron.id)
+            .await
+            .unwrap()
+    );
+    assert!(
+        repository
+            .delete_saved_search(user_id, &iron.id)
+            .await
+            .unwrap()
+    );
+    assert_eq!(
+        repository.list_saved_searches(user_id).await.unwrap().len(),
+        1
+    );
+
+    // 二回続けてお気に入りにしてもエラーにならない
+    for _ in 0..2 {
+        repository
+            .set_favorite(user_id, &vein_id, true)
+            .await
+            .unwrap();
+    }
+    assert_eq!(
+        repository.favorite_vein_ids(user_id).await.unwrap(),
+        std::slice::from_ref(&vein_id)
+    );
+    assert!(
+        repository
+            .favorite_vein_ids(other_user_id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    let favorites = SearchQuery {
+        favorites_of: Some(user_id.to_string()),
+        ..Default::default()
+    };
+    let veins = repository.search_veins(&favorites).await.unwrap();
+    assert_eq!(veins.len(), 1);
+    assert_eq!(veins[0].id, vein_id);
+
+    repository
+        .set_favorite(user_id, &vein_id, false)
+        .await
+        .unwrap();
+    assert!(
+        repository
+            .search_veins(&favorites)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        repository
+            .set_favorite(user_id, &Uuid::new_v4().to_string(), true)
+            .await
+            .unwrap_err()
+            .is_foreign_key_violation()
+    );
+}
+
+async fn exercise_compositions(repository: Arc<dyn VeinRepository>) {
+    // 他の試験の鉱脈と混ざらないよう、鉱石名も一意にする
+    let tin = unique("Tin");
+    let copper = unique("Copper");
+    let mixed_id = Uuid::new_v4().to_string();
+    let copper_id = Uuid::new_v4().to_string();
+    for id in [&mixed_id, &copper_id] {
+        repository
+            .insert_vein(id, &unique("Ores"), 0, None, 0, &None)
+            .await
+            .unwrap();
+    }
+
+    repository
+        .set_composition(
+            &mixed_id,
+            &[
+                VeinOre::new(&copper, OreRole::Primary),
+                VeinOre::new(&tin, OreRole::Sporadic),
+                VeinOre::new(&tin, OreRole::Sporadic),
+            ],
+        )
+        .await
+        .unwrap();
+    repository
+        .set_composition(&copper_id, &[VeinOre::new(&tin, OreRole::Primary)])
+        .await
+        .unwrap();
+    // 置き換えなので、前の構成は残らない
+    repository
+        .set_composition(&copper_id, &[VeinOre::new(&copper, OreRole::Primary)])
+        .await
+        .unwrap();
+
+    let containing = |material: String| SearchQuery {
+        material: Some(material),
+        sort: Some(SortKey::Name),
+        ..Default::default()
+    };
+    let veins = repository
+        .search_veins(&containing(tin.to_uppercase()))
+        .await
+        .unwrap();
+    assert_eq!(veins.len(), 1);
+    assert_eq!(veins[0].id, mixed_id);
+    assert_eq!(
+        veins[0].ores,
+        [
+            VeinOre::new(&copper, OreRole::Primary),
+            VeinOre::new(&tin, OreRole::Sporadic),
+        ]
+    );
+    let veins = repository
+        .search_veins(&containing(copper.clone()))
+        .await
+        .unwrap();
+    assert_eq!(veins.len(), 2);
+
+    let materials = repository.list_materials().await.unwrap();
+    assert!(materials.contains(&tin) && materials.contains(&copper));
+
+    // 構成のない鉱脈は、名前が鉱石と同じでも一致しない
+    let unrecorded_id = Uuid::new_v4().to_string();
+    repository
+        .insert_vein(&unrecorded_id, &tin, 0, None, 0, &None)
+        .await
+        .unwrap();
+    let ids: Vec<String> = repository
+        .search_veins(&containing(tin.clone()))
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|vein| vein.id)
+        .collect();
+    assert_eq!(ids, std::slice::from_ref(&mixed_id));
+
+    assert!(
+        repository
+            .set_composition(
+                &Uuid::new_v4().to_string(),
+                &[VeinOre::new(&tin, OreRole::Primary)]
+            )
+            .await
+            .unwrap_err()
+            .is_foreign_key_violation()
+    );
+}
+
+async fn exercise_auth(repository: Arc<dyn AuthRepository>) {
+    let username = unique("carol");
+
+    let invitation = repository.create_system_invitation(1).await.unwrap();
+    assert!(invitation.invited_by.is_none());
+    assert!(
+        repository
+            .get_invitation_by_token(&invitation.token)
+            .await
+            .unwrap()
+            .is_some()
+    );
+
+    let user = repository
+        .create_user(&username, None, "password123", None, true)
+        .await
+        .unwrap();
+    assert!(repository.has_any_users().await.unwrap());
+    assert!(
+        repository
+            .create_user(&username, None, "password123", None, false)
+            .await
+            .unwrap_err()
+            .is_unique_violation()
+    );
+
+    repository
+        .mark_invitation_used(&invitation.token, &user.id)
+        .await
+        .unwrap();
+    assert!(
+        repository
+            .get_invitation_by_token(&invitation.token)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    let found = repository
+        .get_user_by_username(&username)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.id, user.id);
+    repository
+        .update_password(&user.id, "another-password")
+        .await
+        .unwrap();
+    let updated = repository.get_user_by_id(&user.id).await.unwrap().unwrap();
+    assert_ne!(updated.password_hash, user.password_hash);
+
+    let invitation = repository
+        .create_invitation(Some("dave@example.com"), None, 1)
+        .await
+        .unwrap();
+    assert_eq!(invitation.invited_by.as_deref(), Some("anonymous"));
+}
+
+#[tokio::test]
+async fn in_memory_repositories() {
+    exercise_veins(Arc::new(InMemoryVeinRepository::new())).await;
+    exercise_paging(Arc::new(InMemoryVeinRepository::new())).await;
+    exercise_filters(Arc::new(InMemoryVeinRepository::new())).await;
+    exercise_compositions(Arc::new(InMemoryVeinRepository::new())).await;
+    exercise_saved_searches_and_favorites(
+        Arc::new(InMemoryVeinRepository::new()),
+        &Uuid::new_v4().to_string(),
+        &Uuid::new_v4().to_string(),
+    )
+    .await;
+    exercise_auth(Arc::new(InMemoryAuthRepository::new())).await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "sqlite"),
+    ignore = "needs --features sqlite or TEST_DATABASE_URL"
+)]
+async fn sql_repositories() {
+    let (database_url, sqlite_file) = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => (url, None),
+        Err(_) => common::temporary_sqlite(),
+    };
+    run_pending_migrations(&database_url).await.unwrap();
+    let config = AppConfig::load(ConfigArgs {
+        database_url: Some(database_url),
+        ..Default::default()
+    })
+    .unwrap();
+    let pool = create_diesel_pool(&config).await.unwrap();
+
+    exercise_veins(Arc::new(SqlVeinRepository::new(pool.clone()))).await;
+    exercise_paging(Arc::new(SqlVeinRepository::new(pool.clone()))).await;
+    exercise_filters(Arc::new(SqlVeinRepository::new(pool.clone()))).await;
+    exercise_compositions(Arc::new(SqlVeinRepository::new(pool.clone()))).await;
+    // 保存した検索とお気に入りは実在する利用者に紐づく
+    let auth = SqlAuthRepository::new(pool.clone());
+    let mut user_ids = Vec::new();
+    for _ in 0..2 {
+        let user = auth
+            .create_user(&unique("erin"), None, "password123", None, false)
+            .await
+            .unwrap();
+        user_ids.push(user.id);
+    }
+    exercise_saved_searches_and_favorites(
+        Arc::new(SqlVeinRepository::new(pool.clone())),
+        &user_ids[0],
+        &user_ids[1],
+    )
+    .await;
+    exercise_auth(Arc::new(SqlAuthRepository::new(pool))).await;
+
+    if let Some(path) = sqlite_file {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Reading a search from the query string, and the links and highlights built from it.
+
+use gt6_vein_manager::models::forms::{SearchQuery, SortKey, SortOrder, TextSegment};
+
+#[test]
+fn sort_and_page_links_keep_the_other_parameters() {
+    let query = SearchQuery {
+        name: Some("iron".to_string()),
+        sort: Some(SortKey::Name),
+        order: Some(SortOrder::Asc),
+        page: Some(3),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        query.page_query_string(4),
+        "name=iron&sort=name&order=asc&page=4"
+    );
+    // 同じ列を選ぶと逆順になり、1ページ目に戻る
+    assert_eq!(
+        query.sort_query_string(SortKey::Name),
+        "name=iron&sort=name&order=desc"
+    );
+    assert_eq!(
+        query.sort_query_string(SortKey::CreatedAt),
+        "name=iron&sort=created_at&order=asc"
+    );
+}
+
+#[test]
+fn empty_number_fields_are_ignored() {
+    let query: SearchQuery =
+        serde_urlencoded::from_str("name=&sort=distance&from_x=&from_z=-12&page=&limit=500")
+            .unwrap();
+
+    assert_eq!(query.origin(), (0, -12));
+    assert_eq!(query.page(), 1);
+    assert_eq!(query.limit(), 200);
+}
+
+#[test]
+fn empty_choice_fields_are_ignored() {
+    let query: SearchQuery =
+        serde_urlencoded::from_str("sort=&order=&note_scope=&include_revoked=").unwrap();
+
+    assert_eq!(query.sort_key(), SortKey::CreatedAt);
+    assert_eq!(query.sort_order(), SortOrder::Asc);
+    assert_eq!(query.note_scope, None);
+    assert_eq!(query.include_revoked, None);
+
+    let query: SearchQuery = serde_urlencoded::from_str("sort=status&order=desc").unwrap();
+    assert_eq!(query.sort_key(), SortKey::Status);
+    assert_eq!(query.sort_order(), SortOrder::Desc);
+    assert!(serde_urlencoded::from_str::<SearchQuery>("sort=height").is_err());
+}
+
+#[test]
+fn highlight_ignores_case_and_marks_every_keyword() {
+    let query = SearchQuery {
+        text: Some("TIN  ore tin".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(query.text_terms(), ["tin", "ore"]);
+
+    let segment = |text: &str, matched| TextSegment {
+        text: text.to_string(),
+        matched,
+    };
+    assert_eq!(
+        query.highlight("Tinore 錫 Tin"),
+        [
+            segment("Tinore", true),
+            segment(" 錫 ", false),
+            segment("Tin", true),
+        ]
+    );
+    assert_eq!(query.highlight(""), []);
+}